@@ -0,0 +1,10 @@
+#![no_main]
+
+use ever_archive::*;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(archive) = ArchiveData::new(data) {
+        let _ = archive.check();
+    }
+});
@@ -0,0 +1,12 @@
+#![no_main]
+
+use ever_archive::*;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|filename: &str| {
+    if let Ok(id) = PackageEntryId::from_filename(filename) {
+        // Parsed ids must survive the roundtrip
+        let parsed = PackageEntryId::from_filename(&id.filename()).unwrap();
+        assert_eq!(parsed, id);
+    }
+});
@@ -0,0 +1,17 @@
+#![no_main]
+
+use ever_archive::*;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(reader) = ArchivePackageViewReader::new(data) {
+        let mut reader = reader.with_strict_eof(true);
+        while let Ok(Some(entry)) = reader.read_next() {
+            assert!(entry.header_offset + entry.frame_len <= data.len());
+            assert_eq!(&data[entry.data_offset..][..entry.data.len()], entry.data);
+        }
+    }
+
+    let mut reader = ArchivePackageSalvageReader::new(data);
+    while reader.read_next().is_some() {}
+});
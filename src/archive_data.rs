@@ -1,9 +1,18 @@
 use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::ops::Range;
+use std::sync::Arc;
 
+use once_cell::sync::OnceCell;
 use ton_block::Deserializable;
 
 use crate::archive_package::*;
+use crate::archive_policy::*;
+use crate::key_block_validators::*;
 use crate::package_entry_id::*;
+use crate::validation_report::*;
+
+/// Archive data which owns the package and can be shared between threads
+pub type OwnedArchiveData = ArchiveData<'static>;
 
 pub struct ArchiveData<'a> {
     pub mc_block_ids: BTreeMap<u32, ton_block::BlockIdExt>,
@@ -13,49 +22,166 @@ pub struct ArchiveData<'a> {
 impl<'a> ArchiveData<'a> {
     pub const MAX_MC_BLOCK_COUNT: usize = 100;
 
+    /// Max gen_utime difference between the linked blocks which is not reported as suspicious
+    pub const MAX_GEN_UTIME_GAP: u32 = 3600;
+
+    /// Reads all entries and deserializes all blocks and proofs
     pub fn new(data: &'a [u8]) -> Result<Self, ArchiveDataError> {
-        let mut reader = ArchivePackageViewReader::new(data)?;
+        Self::read(data, false)
+    }
+
+    /// Reads all entries, but deserializes blocks and proofs only on first access
+    pub fn new_lazy(data: &'a [u8]) -> Result<Self, ArchiveDataError> {
+        Self::read(data, true)
+    }
+
+    /// Builds archive data from already read package entries
+    pub fn from_entries<I>(entries: I) -> Result<Self, ArchiveDataError>
+    where
+        I: IntoIterator<Item = &'a ArchivePackageEntry>,
+    {
+        Self::read_entries(entries, false)
+    }
+
+    /// Same as [`ArchiveData::from_entries`], but deserializes blocks and proofs
+    /// only on first access
+    pub fn from_entries_lazy<I>(entries: I) -> Result<Self, ArchiveDataError>
+    where
+        I: IntoIterator<Item = &'a ArchivePackageEntry>,
+    {
+        Self::read_entries(entries, true)
+    }
+
+    fn read_entries<I>(entries: I, lazy: bool) -> Result<Self, ArchiveDataError>
+    where
+        I: IntoIterator<Item = &'a ArchivePackageEntry>,
+    {
+        let entries = entries
+            .into_iter()
+            .map(|entry| (entry.name.as_str(), EntryBytes::Borrowed(&entry.data)))
+            .collect::<Vec<_>>();
+        Self::from_raw_entries(&entries, lazy)
+    }
+
+    fn read(data: &'a [u8], lazy: bool) -> Result<Self, ArchiveDataError> {
+        Self::read_with(data, |entry| EntryBytes::Borrowed(entry.data), lazy)
+    }
+
+    fn read_with<'p, F>(
+        package: &'p [u8],
+        make_bytes: F,
+        lazy: bool,
+    ) -> Result<Self, ArchiveDataError>
+    where
+        F: Fn(&ArchivePackageEntryView<'p>) -> EntryBytes<'a>,
+    {
+        let mut reader = ArchivePackageViewReader::new(package)?.with_strict_eof(true);
+
+        let mut entries = Vec::new();
+        let mut read_error = None;
+        loop {
+            match reader.read_next() {
+                Ok(Some(entry)) => entries.push((entry.name, make_bytes(&entry))),
+                Ok(None) => break,
+                Err(e) => {
+                    // NOTE: entries before the broken one have lower offsets,
+                    // so their errors must be reported first
+                    read_error = Some(e);
+                    break;
+                }
+            }
+        }
+
+        let res = Self::from_raw_entries(&entries, lazy)?;
+        match read_error {
+            Some(e) => Err(e.into()),
+            None => Ok(res),
+        }
+    }
 
-        let mut res = ArchiveData {
+    /// Deserializes entries on the rayon thread pool.
+    /// The error of the entry with the lowest offset is returned.
+    /// Conflicts between entries are checked after all entries are parsed
+    fn from_raw_entries(
+        entries: &[(&str, EntryBytes<'a>)],
+        lazy: bool,
+    ) -> Result<Self, ArchiveDataError> {
+        use rayon::prelude::*;
+
+        let parsed = entries
+            .par_iter()
+            .map(|(name, data)| ParsedEntry::parse(name, data.clone(), lazy))
+            .collect::<Vec<_>>();
+
+        let mut res = Self {
             mc_block_ids: Default::default(),
             blocks: Default::default(),
         };
+        for entry in parsed {
+            res.insert_parsed(entry?);
+        }
 
-        while let Some(entry) = reader.read_next()? {
-            match PackageEntryId::from_filename(entry.name)? {
-                PackageEntryId::Block(id) => {
-                    let block = deserialize_block(&id, entry.data)?;
-
-                    res.blocks
-                        .entry(id.clone())
-                        .or_insert_with(ArchiveDataEntry::default)
-                        .block = Some((block, entry.data));
-                    if id.shard_id.is_masterchain() {
-                        res.mc_block_ids.insert(id.seq_no, id);
-                    }
-                }
-                PackageEntryId::Proof(id) if id.shard_id.is_masterchain() => {
-                    let proof = deserialize_block_proof(&id, entry.data, false)?;
-
-                    res.blocks
-                        .entry(id.clone())
-                        .or_insert_with(ArchiveDataEntry::default)
-                        .proof = Some((proof, entry.data));
-                    res.mc_block_ids.insert(id.seq_no, id);
-                }
-                PackageEntryId::ProofLink(id) if !id.shard_id.is_masterchain() => {
-                    let proof = deserialize_block_proof(&id, entry.data, true)?;
+        let mut conflicts = find_duplicate_entries(entries);
+        conflicts.extend(res.find_forks());
+        if !conflicts.is_empty() {
+            return Err(ArchiveDataError::ConflictingEntries(conflicts));
+        }
 
-                    res.blocks
-                        .entry(id.clone())
-                        .or_insert_with(ArchiveDataEntry::default)
-                        .proof = Some((proof, entry.data));
+        Ok(res)
+    }
+
+    /// Searches for different blocks with the same shard and seqno
+    fn find_forks(&self) -> Vec<EntryConflict> {
+        let mut ids_by_seqno = HashMap::<_, Vec<_>>::new();
+        for id in self.blocks.keys() {
+            ids_by_seqno
+                .entry((id.shard_id, id.seq_no))
+                .or_default()
+                .push(id.clone());
+        }
+
+        let mut forks = ids_by_seqno
+            .into_iter()
+            .filter(|(_, ids)| ids.len() > 1)
+            .map(|((shard_id, seq_no), ids)| EntryConflict::Fork {
+                shard_id,
+                seq_no,
+                ids,
+            })
+            .collect::<Vec<_>>();
+        forks.sort_by(|left, right| left.ids().cmp(right.ids()));
+        forks
+    }
+
+    fn insert_parsed(&mut self, entry: ParsedEntry<'a>) {
+        match entry {
+            ParsedEntry::Block { id, block, data } => {
+                self.entry_mut(&id).block = Some(LazyData::new(data, block));
+                if id.shard_id.is_masterchain() {
+                    self.mc_block_ids.insert(id.seq_no, id);
+                }
+            }
+            ParsedEntry::Proof {
+                id,
+                proof,
+                data,
+                is_link,
+            } => {
+                let entry = self.entry_mut(&id);
+                entry.proof = Some(LazyData::new(data, proof));
+                entry.is_link = is_link;
+                if id.shard_id.is_masterchain() {
+                    self.mc_block_ids.insert(id.seq_no, id);
                 }
-                _ => continue,
             }
+            ParsedEntry::Skipped => {}
         }
+    }
 
-        Ok(res)
+    fn entry_mut(&mut self, id: &ton_block::BlockIdExt) -> &mut ArchiveDataEntry<'a> {
+        self.blocks
+            .entry(id.clone())
+            .or_insert_with(|| ArchiveDataEntry::new(id.clone()))
     }
 
     pub fn lowest_mc_id(&self) -> Option<&ton_block::BlockIdExt> {
@@ -66,64 +192,714 @@ impl<'a> ArchiveData<'a> {
         self.mc_block_ids.values().rev().next()
     }
 
+    /// Collects the information required to check that the next archive continues this one
+    pub fn boundary(&self) -> Result<ArchiveBoundary, ArchiveDataError> {
+        let (first_mc_block, last_mc_block) = match (self.lowest_mc_id(), self.highest_mc_id()) {
+            (Some(first), Some(last)) => (first.clone(), last.clone()),
+            _ => return Err(ArchiveDataError::EmptyArchive),
+        };
+
+        // All blocks which were committed at the end of the archive
+        let mut top_blocks = BTreeSet::new();
+        top_blocks.insert(last_mc_block.clone());
+
+        let last_mc_entry = self
+            .blocks
+            .get(&last_mc_block)
+            .ok_or(ArchiveDataError::BlockDataNotFound)?;
+        let (block, _) = last_mc_entry.block()?;
+        for id in read_top_shard_blocks(block)? {
+            top_blocks.insert(id);
+        }
+
+        // All links to the blocks which are not in this archive
+        let mut external_links = Vec::new();
+        for (id, entry) in &self.blocks {
+            let (block, _) = entry.block()?;
+            let info = block
+                .read_info()
+                .map_err(|_| ArchiveDataError::InvalidBlockData)?;
+
+            for prev_id in prev_block_ids(id, &info)? {
+                if !self.blocks.contains_key(&prev_id) {
+                    external_links.push((id.clone(), prev_id));
+                }
+            }
+        }
+
+        Ok(ArchiveBoundary {
+            first_mc_block,
+            last_mc_block,
+            top_blocks,
+            external_links,
+        })
+    }
+
+    /// Checks the archive without layout rules, returning the first found problem
     pub fn check(&self) -> Result<(), ArchiveDataError> {
-        let mc_block_count = self.mc_block_ids.len();
+        self.check_with_policy(&ArchivePolicy::default())
+    }
 
-        let (left, right) = match (self.lowest_mc_id(), self.highest_mc_id()) {
-            (Some(left), Some(right)) => (left.seq_no, right.seq_no),
-            _ => return Err(ArchiveDataError::EmptyArchive),
+    /// Checks the archive, returning the first found problem
+    pub fn check_with_policy(&self, policy: &ArchivePolicy) -> Result<(), ArchiveDataError> {
+        self.validate_with_policy(policy).into_result()
+    }
+
+    /// Checks the archive without layout rules, collecting all found problems
+    pub fn validate(&self) -> ValidationReport {
+        self.validate_with_policy(&ArchivePolicy::default())
+    }
+
+    /// Checks the archive, collecting all found problems
+    pub fn validate_with_policy(&self, policy: &ArchivePolicy) -> ValidationReport {
+        let mut report = ValidationReport::default();
+
+        if self.mc_block_ids.is_empty() {
+            report.push(ValidationProblem::archive(ArchiveDataError::EmptyArchive));
+            return report;
+        }
+
+        self.validate_entries(&mut report);
+
+        // NOTE: blocks and proofs which can't be deserialized are reported
+        // by `validate_entries`, so other checks just skip them
+        let infos = self.read_infos();
+        self.validate_proofs(&infos, &mut report);
+        self.validate_shard_references(&mut report);
+        self.validate_master_refs(&infos, &mut report);
+        self.validate_policy(policy, &infos, &mut report);
+        self.validate_masterchain_continuity(&mut report);
+        self.validate_block_links(&infos, &mut report);
+        self.validate_time(&infos, &mut report);
+
+        report
+    }
+
+    /// Checks that all blocks have data and proofs which can be deserialized
+    fn validate_entries(&self, report: &mut ValidationReport) {
+        for (id, entry) in &self.blocks {
+            if let Err(e) = entry.block() {
+                report.push(ValidationProblem::block(id, e));
+            }
+            if let Err(e) = entry.proof() {
+                report.push(ValidationProblem::proof(id, e));
+            }
+        }
+    }
+
+    /// Checks that proofs contain valid Merkle proofs of the block headers
+    fn validate_proofs(&self, infos: &BlockInfos<'_>, report: &mut ValidationReport) {
+        for (id, entry) in &self.blocks {
+            let (proof, _) = match entry.proof() {
+                Ok(proof) => proof,
+                Err(_) => continue,
+            };
+
+            let result =
+                read_proven_block_info(proof).and_then(|proven_info| match infos.get(id) {
+                    Some(info) => compare_block_info(info, &proven_info),
+                    None => Ok(()),
+                });
+
+            if let Err(e) = result {
+                report.push(ValidationProblem::proof(id, e));
+            }
+        }
+    }
+
+    /// Checks that top shard blocks referenced by masterchain blocks are in the archive
+    /// and that all shard blocks in the archive are committed by masterchain blocks
+    fn validate_shard_references(&self, report: &mut ValidationReport) {
+        let mut shard_blocks = self.blocks_by_shard();
+        shard_blocks.remove(&ton_block::ShardIdent::masterchain());
+
+        // Max committed seqno for each referenced shard
+        let mut committed = HashMap::<ton_block::ShardIdent, u32>::new();
+
+        // Top shard blocks of the previous masterchain block
+        let mut prev_top_blocks: Option<(u32, BTreeSet<ton_block::BlockIdExt>)> = None;
+
+        for mc_id in self.mc_block_ids.values() {
+            let prev = prev_top_blocks
+                .take()
+                .filter(|(seqno, _)| seqno.checked_add(1) == Some(mc_id.seq_no))
+                .map(|(_, top_blocks)| top_blocks);
+
+            let block = match self.blocks.get(mc_id).map(ArchiveDataEntry::block) {
+                Some(Ok((block, _))) => block,
+                _ => continue,
+            };
+            let top_blocks = match read_top_shard_blocks(block) {
+                Ok(top_blocks) => top_blocks,
+                Err(e) => {
+                    report.push(ValidationProblem::block(mc_id, e));
+                    continue;
+                }
+            };
+
+            for id in &top_blocks {
+                let seqno = committed.entry(id.shard_id).or_default();
+                *seqno = std::cmp::max(*seqno, id.seq_no);
+
+                if self.blocks.contains_key(id) {
+                    continue;
+                }
+
+                let archived = shard_blocks
+                    .get(&id.shard_id)
+                    .and_then(|blocks| blocks.get(&id.seq_no));
+                if let Some(&found) = archived {
+                    report.push(ValidationProblem::block(
+                        mc_id,
+                        ArchiveDataError::ShardBlockHashMismatch {
+                            expected: id.clone(),
+                            found: found.clone(),
+                        },
+                    ));
+                    continue;
+                }
+
+                let is_missing = match &prev {
+                    // Blocks committed by this masterchain block must be in the archive
+                    Some(prev) => !prev.contains(id),
+                    // Blocks before the shard range belong to the previous archive
+                    None => {
+                        let lowest_seqno = shard_blocks
+                            .iter()
+                            .filter(|&(shard, _)| intersects(shard, &id.shard_id))
+                            .filter_map(|(_, blocks)| blocks.keys().next().copied())
+                            .min();
+                        matches!(lowest_seqno, Some(lowest_seqno) if id.seq_no >= lowest_seqno)
+                    }
+                };
+                if is_missing {
+                    report.push(ValidationProblem::block(
+                        mc_id,
+                        ArchiveDataError::MissingShardBlock { id: id.clone() },
+                    ));
+                }
+            }
+
+            prev_top_blocks = Some((mc_id.seq_no, top_blocks.into_iter().collect()));
+        }
+
+        for blocks in shard_blocks.values() {
+            for &id in blocks.values() {
+                let is_committed = committed
+                    .iter()
+                    .any(|(shard, seqno)| intersects(shard, &id.shard_id) && *seqno >= id.seq_no);
+                if !is_committed {
+                    report.push(ValidationProblem::block(
+                        id,
+                        ArchiveDataError::UncommittedShardBlock { id: id.clone() },
+                    ));
+                }
+            }
+        }
+    }
+
+    /// Checks that shard blocks refer to the masterchain blocks from the archive
+    fn validate_master_refs(&self, infos: &BlockInfos<'_>, report: &mut ValidationReport) {
+        for (&id, info) in infos {
+            if id.shard_id.is_masterchain() {
+                continue;
+            }
+
+            if let Err(e) = check_master_ref(&self.mc_block_ids, info) {
+                report.push(ValidationProblem::block(id, e));
+            }
+        }
+    }
+
+    /// Checks signatures of all masterchain blocks in seqno order. Validator sets are
+    /// taken from the known key blocks or from the key blocks in the archive
+    /// which were verified before. Known key blocks are never replaced
+    pub fn validate_signatures(&self, key_blocks: &KeyBlockSet) -> ValidationReport {
+        let mut report = ValidationReport::default();
+        let mut archive_key_blocks = KeyBlockSet::default();
+
+        // NOTE: invalid blocks and proofs are reported by `validate`
+        let infos = self.read_mc_infos();
+        for (id, block, proof, info) in self.mc_blocks_with_proofs(&infos) {
+            let key_block_seqno = info.prev_key_block_seqno();
+            let result = if key_blocks.contains(key_block_seqno) {
+                key_blocks.check_signatures(info, proof)
+            } else {
+                archive_key_blocks.check_signatures(info, proof)
+            };
+
+            if let Err(e) = result {
+                report.push(ValidationProblem::proof(id, e));
+                continue;
+            }
+
+            if info.key_block() && !key_blocks.contains(id.seq_no) {
+                match KeyBlockValidators::from_key_block(block) {
+                    Ok(validators) => archive_key_blocks.insert(validators),
+                    Err(e) => report.push(ValidationProblem::block(id, e)),
+                }
+            }
+        }
+
+        report
+    }
+
+    /// Returns all key blocks with their proofs. Signatures are not checked
+    pub fn key_blocks(&self) -> Vec<KeyBlockEntry> {
+        let infos = self.read_mc_infos();
+        self.mc_blocks_with_proofs(&infos)
+            .filter(|(_, _, _, info)| info.key_block())
+            .map(|(_, block, proof, _)| KeyBlockEntry {
+                block: block.clone(),
+                proof: proof.clone(),
+            })
+            .collect()
+    }
+
+    /// Iterates over valid masterchain blocks and proofs in seqno order
+    fn mc_blocks_with_proofs<'s>(
+        &'s self,
+        infos: &'s BlockInfos<'s>,
+    ) -> impl Iterator<
+        Item = (
+            &'s ton_block::BlockIdExt,
+            &'s ton_block::Block,
+            &'s ton_block::BlockProof,
+            &'s ton_block::BlockInfo,
+        ),
+    > + 's {
+        self.mc_block_ids.values().filter_map(move |id| {
+            let entry = self.blocks.get(id)?;
+            let (block, _) = entry.block().ok()?;
+            let (proof, _) = entry.proof().ok()?;
+            Some((id, block, proof, infos.get(id)?))
+        })
+    }
+
+    /// Checks the archive size and alignment of masterchain blocks
+    fn validate_policy(
+        &self,
+        policy: &ArchivePolicy,
+        infos: &BlockInfos<'_>,
+        report: &mut ValidationReport,
+    ) {
+        let (first_seqno, last_seqno) = match (
+            self.mc_block_ids.keys().next(),
+            self.mc_block_ids.keys().next_back(),
+        ) {
+            (Some(first), Some(last)) => (*first, *last),
+            _ => return,
         };
 
-        // NOTE: blocks are stored in BTreeSet so keys are ordered integers
-        if (left as usize) + mc_block_count != (right as usize) + 1 {
-            return Err(ArchiveDataError::InconsistentMasterchainBlocks);
+        let count = self.mc_block_ids.len();
+        if let Some(max) = policy.max_mc_block_count {
+            if count > max {
+                report.push(ValidationProblem::archive(
+                    ArchiveDataError::TooManyMasterchainBlocks { count, max },
+                ));
+            }
         }
 
-        // Group all block ids by shards
-        let mut map = HashMap::default();
-        for block_id in self.blocks.keys() {
-            map.entry(block_id.shard_id)
-                .or_insert_with(BTreeSet::new)
-                .insert(block_id.seq_no);
+        let mut mc_blocks = self.mc_block_ids.values().map(|id| (id, infos.get(id)));
+        let first_info = mc_blocks.next().and_then(|(_, info)| info);
+
+        if policy.key_block_first {
+            for (id, info) in mc_blocks {
+                if matches!(info, Some(info) if info.key_block()) {
+                    report.push(ValidationProblem::block(
+                        id,
+                        ArchiveDataError::UnexpectedKeyBlock { seqno: id.seq_no },
+                    ));
+                }
+            }
         }
 
-        // Check consistency
-        for (shard_ident, blocks) in &map {
-            let mut block_seqnos = blocks.iter();
+        // NOTE: the node starts a new slice at each key block
+        let slice_size = policy.slice_size.filter(|size| *size > 0);
+        if let (Some(slice_size), Some(first_info)) = (slice_size, first_info) {
+            let key_block_seqno = if first_info.key_block() {
+                first_seqno
+            } else {
+                first_info.prev_key_block_seqno()
+            };
 
-            // Skip empty shards
-            let mut prev = match block_seqnos.next() {
-                Some(seqno) => *seqno,
-                None => continue,
+            let offset = first_seqno.saturating_sub(key_block_seqno);
+            if offset % slice_size != 0 {
+                report.push(ValidationProblem::archive(
+                    ArchiveDataError::UnalignedArchive {
+                        first_seqno,
+                        key_block_seqno,
+                        slice_size,
+                    },
+                ));
+            }
+
+            let slice_end =
+                key_block_seqno as u64 + ((offset / slice_size) as u64 + 1) * slice_size as u64;
+            if last_seqno as u64 >= slice_end {
+                report.push(ValidationProblem::archive(
+                    ArchiveDataError::ArchiveCrossesSliceBoundary {
+                        first_seqno,
+                        last_seqno,
+                        key_block_seqno,
+                        slice_size,
+                    },
+                ));
+            }
+        }
+    }
+
+    fn validate_masterchain_continuity(&self, report: &mut ValidationReport) {
+        // NOTE: blocks are stored in BTreeMap so keys are ordered integers
+        let mut seqnos = self.mc_block_ids.keys();
+        let mut prev = match seqnos.next() {
+            Some(seqno) => *seqno,
+            None => return,
+        };
+
+        for &seqno in seqnos {
+            if seqno != prev + 1 {
+                report.push(ValidationProblem::archive(
+                    ArchiveDataError::MissingMasterchainBlocks {
+                        first: prev + 1,
+                        last: seqno - 1,
+                    },
+                ));
+            }
+            prev = seqno;
+        }
+    }
+
+    /// Checks that previous blocks referenced by `prev_ref` are either in the archive
+    /// with the same hashes or belong to the previous archive
+    fn validate_block_links(&self, infos: &BlockInfos<'_>, report: &mut ValidationReport) {
+        let blocks_by_shard = self.blocks_by_shard();
+
+        for (&id, info) in infos {
+            let prev_ids = match prev_block_ids(id, info) {
+                Ok(prev_ids) => prev_ids,
+                Err(e) => {
+                    report.push(ValidationProblem::block(id, e));
+                    continue;
+                }
             };
 
-            // Iterate through all blocks in shard
-            for &seqno in block_seqnos {
-                // Search either for the previous known block in the same shard
-                // or in other shards in case of merge/split
-                if seqno != prev + 1 && !contains_previous_block(&map, shard_ident, seqno - 1) {
-                    return Err(ArchiveDataError::InconsistentShardchainBlock {
-                        shard_ident: *shard_ident,
-                        seqno,
-                    });
+            for prev_id in prev_ids {
+                if self.blocks.contains_key(&prev_id) {
+                    continue;
+                }
+
+                let archived = blocks_by_shard
+                    .get(&prev_id.shard_id)
+                    .and_then(|blocks| blocks.get(&prev_id.seq_no));
+                if let Some(&found) = archived {
+                    report.push(ValidationProblem::block(
+                        id,
+                        ArchiveDataError::PrevBlockHashMismatch {
+                            expected: prev_id,
+                            found: found.clone(),
+                        },
+                    ));
+                    continue;
+                }
+
+                // NOTE: masterchain gaps are reported by `validate_masterchain_continuity`
+                if prev_id.shard_id.is_masterchain() {
+                    continue;
+                }
+
+                // Previous block can only be missing if it belongs to the previous archive
+                let has_lower_blocks = blocks_by_shard
+                    .iter()
+                    .filter(|&(shard, _)| intersects(shard, &prev_id.shard_id))
+                    .any(|(_, blocks)| blocks.range(..prev_id.seq_no).next().is_some());
+                if has_lower_blocks {
+                    report.push(ValidationProblem::block(
+                        id,
+                        ArchiveDataError::UnlinkedBlock {
+                            id: id.clone(),
+                            prev_id,
+                        },
+                    ));
                 }
-                // Update last known seqno for this shard
-                prev = seqno;
             }
         }
+    }
 
-        // Archive is not empty and all blocks are contiguous
-        Ok(())
+    /// Checks logical time ranges and gen_utime of the linked blocks
+    fn validate_time(&self, infos: &BlockInfos<'_>, report: &mut ValidationReport) {
+        let check_gen_utime_gap =
+            |report: &mut ValidationReport,
+             id: &ton_block::BlockIdExt,
+             info: &ton_block::BlockInfo,
+             other_id: &ton_block::BlockIdExt,
+             other_info: &ton_block::BlockInfo| {
+                let gap = info
+                    .gen_utime()
+                    .as_u32()
+                    .abs_diff(other_info.gen_utime().as_u32());
+                if gap > Self::MAX_GEN_UTIME_GAP {
+                    report.push(
+                        ValidationProblem::block(
+                            id,
+                            ArchiveDataError::SuspiciousGenUtimeGap {
+                                other_id: other_id.clone(),
+                                gap,
+                            },
+                        )
+                        .with_severity(Severity::Warning),
+                    );
+                }
+            };
+
+        for (&id, info) in infos {
+            if info.start_lt() > info.end_lt() {
+                report.push(ValidationProblem::block(
+                    id,
+                    ArchiveDataError::InvalidLtRange {
+                        start_lt: info.start_lt(),
+                        end_lt: info.end_lt(),
+                    },
+                ));
+            }
+
+            // NOTE: invalid links are reported by `validate_block_links`
+            for prev_id in prev_block_ids(id, info).unwrap_or_default() {
+                let prev_info = match infos.get(&prev_id) {
+                    Some(prev_info) => prev_info,
+                    None => continue,
+                };
+
+                if info.start_lt() <= prev_info.end_lt() {
+                    report.push(ValidationProblem::block(
+                        id,
+                        ArchiveDataError::LtOverlap {
+                            prev_id: prev_id.clone(),
+                            prev_end_lt: prev_info.end_lt(),
+                            start_lt: info.start_lt(),
+                        },
+                    ));
+                }
+                if info.gen_utime().as_u32() < prev_info.gen_utime().as_u32() {
+                    report.push(ValidationProblem::block(
+                        id,
+                        ArchiveDataError::GenUtimeDecreased {
+                            prev_id: prev_id.clone(),
+                            prev_gen_utime: prev_info.gen_utime().as_u32(),
+                            gen_utime: info.gen_utime().as_u32(),
+                        },
+                    ));
+                }
+                check_gen_utime_gap(report, id, info, &prev_id, prev_info);
+            }
+
+            // NOTE: invalid master refs are reported by `validate_master_refs`
+            let master_ref = match info.read_master_ref() {
+                Ok(Some(master_ref)) => master_ref.master,
+                _ => continue,
+            };
+
+            if info.start_lt() <= master_ref.end_lt {
+                report.push(ValidationProblem::block(
+                    id,
+                    ArchiveDataError::MasterRefLtOverlap {
+                        master_end_lt: master_ref.end_lt,
+                        start_lt: info.start_lt(),
+                    },
+                ));
+            }
+
+            let (mc_id, mc_info) = match self
+                .mc_block_ids
+                .get(&master_ref.seq_no)
+                .and_then(|mc_id| Some((mc_id, infos.get(mc_id)?)))
+            {
+                Some(mc) => mc,
+                None => continue,
+            };
+
+            if mc_info.end_lt() != master_ref.end_lt {
+                report.push(ValidationProblem::block(
+                    id,
+                    ArchiveDataError::MasterRefLtMismatch {
+                        expected: mc_info.end_lt(),
+                        found: master_ref.end_lt,
+                    },
+                ));
+            }
+            check_gen_utime_gap(report, id, info, mc_id, mc_info);
+        }
+    }
+
+    /// Reads infos of all blocks which can be deserialized
+    fn read_infos(&self) -> BlockInfos<'_> {
+        self.read_infos_of(self.blocks.keys())
+    }
+
+    /// Reads infos of masterchain blocks which can be deserialized.
+    /// Shard blocks are not deserialized
+    fn read_mc_infos(&self) -> BlockInfos<'_> {
+        self.read_infos_of(self.mc_block_ids.values())
+    }
+
+    fn read_infos_of<'s, I>(&'s self, ids: I) -> BlockInfos<'s>
+    where
+        I: Iterator<Item = &'s ton_block::BlockIdExt>,
+    {
+        ids.filter_map(|id| {
+            let (block, _) = self.blocks.get(id)?.block().ok()?;
+            Some((id, block.read_info().ok()?))
+        })
+        .collect()
+    }
+
+    /// Groups all block ids by shards
+    fn blocks_by_shard(&self) -> BlocksByShard<'_> {
+        let mut blocks_by_shard = BlocksByShard::new();
+        for id in self.blocks.keys() {
+            blocks_by_shard
+                .entry(id.shard_id)
+                .or_default()
+                .insert(id.seq_no, id);
+        }
+        blocks_by_shard
+    }
+}
+
+impl ArchiveData<'static> {
+    /// Reads all entries and deserializes all blocks and proofs.
+    /// Entries keep the package alive
+    pub fn from_shared(data: Arc<[u8]>) -> Result<Self, ArchiveDataError> {
+        Self::read_shared(data, false)
+    }
+
+    /// Same as [`ArchiveData::from_shared`], but deserializes blocks and proofs
+    /// only on first access
+    pub fn from_shared_lazy(data: Arc<[u8]>) -> Result<Self, ArchiveDataError> {
+        Self::read_shared(data, true)
+    }
+
+    fn read_shared(data: Arc<[u8]>, lazy: bool) -> Result<Self, ArchiveDataError> {
+        Self::read_with(
+            &data,
+            |entry| {
+                let range = entry.data_offset..entry.data_offset + entry.data.len();
+                EntryBytes::Shared(data.clone(), range)
+            },
+            lazy,
+        )
+    }
+}
+
+enum ParsedEntry<'a> {
+    Block {
+        id: ton_block::BlockIdExt,
+        block: Option<ton_block::Block>,
+        data: EntryBytes<'a>,
+    },
+    Proof {
+        id: ton_block::BlockIdExt,
+        proof: Option<ton_block::BlockProof>,
+        data: EntryBytes<'a>,
+        is_link: bool,
+    },
+    Skipped,
+}
+
+impl<'a> ParsedEntry<'a> {
+    fn parse(name: &str, data: EntryBytes<'a>, lazy: bool) -> Result<Self, ArchiveDataError> {
+        Ok(match PackageEntryId::from_filename(name)? {
+            PackageEntryId::Block(id) => Self::Block {
+                block: if lazy {
+                    None
+                } else {
+                    Some(deserialize_block(&id, data.as_slice())?)
+                },
+                id,
+                data,
+            },
+            PackageEntryId::Proof(id) if id.shard_id.is_masterchain() => Self::Proof {
+                proof: if lazy {
+                    None
+                } else {
+                    Some(deserialize_block_proof(&id, data.as_slice(), false)?)
+                },
+                id,
+                data,
+                is_link: false,
+            },
+            PackageEntryId::ProofLink(id) if !id.shard_id.is_masterchain() => Self::Proof {
+                proof: if lazy {
+                    None
+                } else {
+                    Some(deserialize_block_proof(&id, data.as_slice(), true)?)
+                },
+                id,
+                data,
+                is_link: true,
+            },
+            _ => Self::Skipped,
+        })
     }
 }
 
-#[derive(Default)]
+/// Block and proof of the archive entry. They are deserialized on first access
+/// through [`block`](Self::block) and [`proof`](Self::proof)
 pub struct ArchiveDataEntry<'a> {
-    pub block: Option<WithData<'a, ton_block::Block>>,
-    pub proof: Option<WithData<'a, ton_block::BlockProof>>,
+    id: ton_block::BlockIdExt,
+    block: Option<LazyData<'a, ton_block::Block>>,
+    proof: Option<LazyData<'a, ton_block::BlockProof>>,
+    is_link: bool,
 }
 
-impl ArchiveDataEntry<'_> {
+impl<'a> ArchiveDataEntry<'a> {
+    fn new(id: ton_block::BlockIdExt) -> Self {
+        Self {
+            id,
+            block: None,
+            proof: None,
+            is_link: false,
+        }
+    }
+
+    pub fn id(&self) -> &ton_block::BlockIdExt {
+        &self.id
+    }
+
+    /// Raw block data if the archive contains it
+    pub fn block_data(&self) -> Option<&[u8]> {
+        self.block.as_ref().map(|block| block.data.as_slice())
+    }
+
+    /// Raw proof or prooflink data if the archive contains it
+    pub fn proof_data(&self) -> Option<&[u8]> {
+        self.proof.as_ref().map(|proof| proof.data.as_slice())
+    }
+
+    /// Returns the block, deserializing it on first access
+    pub fn block(&self) -> Result<RefWithData<ton_block::Block>, ArchiveDataError> {
+        let block = self
+            .block
+            .as_ref()
+            .ok_or(ArchiveDataError::BlockDataNotFound)?;
+        let value = block
+            .value
+            .get_or_try_init(|| deserialize_block(&self.id, block.data.as_slice()))?;
+        Ok((value, block.data.as_slice()))
+    }
+
+    /// Returns the proof, deserializing it on first access
+    pub fn proof(&self) -> Result<RefWithData<ton_block::BlockProof>, ArchiveDataError> {
+        let proof = self
+            .proof
+            .as_ref()
+            .ok_or(ArchiveDataError::BlockProofNotFound)?;
+        let value = proof.value.get_or_try_init(|| {
+            deserialize_block_proof(&self.id, proof.data.as_slice(), self.is_link)
+        })?;
+        Ok((value, proof.data.as_slice()))
+    }
+
     pub fn get_data(
         &self,
     ) -> Result<
@@ -133,15 +909,269 @@ impl ArchiveDataEntry<'_> {
         ),
         ArchiveDataError,
     > {
-        let block = match &self.block {
-            Some((block, data)) => (block, *data),
-            None => return Err(ArchiveDataError::BlockDataNotFound),
-        };
-        let block_proof = match &self.proof {
-            Some((proof, data)) => (proof, *data),
-            None => return Err(ArchiveDataError::BlockProofNotFound),
+        Ok((self.block()?, self.proof()?))
+    }
+}
+
+struct LazyData<'a, T> {
+    data: EntryBytes<'a>,
+    value: OnceCell<T>,
+}
+
+impl<'a, T> LazyData<'a, T> {
+    fn new(data: EntryBytes<'a>, value: Option<T>) -> Self {
+        Self {
+            data,
+            value: match value {
+                Some(value) => OnceCell::with_value(value),
+                None => OnceCell::new(),
+            },
+        }
+    }
+}
+
+/// Edges of the archive used to check continuity between adjacent archives
+pub struct ArchiveBoundary {
+    pub first_mc_block: ton_block::BlockIdExt,
+    pub last_mc_block: ton_block::BlockIdExt,
+    /// Last masterchain block and all shard blocks referenced by it
+    pub top_blocks: BTreeSet<ton_block::BlockIdExt>,
+    /// Pairs of blocks and their previous blocks which are not in the archive
+    pub external_links: Vec<(ton_block::BlockIdExt, ton_block::BlockIdExt)>,
+}
+
+impl ArchiveBoundary {
+    /// Checks that the next archive starts right after this one
+    pub fn check_next(&self, next: &ArchiveBoundary) -> Result<(), ArchiveDataError> {
+        if next.first_mc_block.seq_no <= self.last_mc_block.seq_no {
+            return Err(ArchiveDataError::OverlappingArchives {
+                last_seqno: self.last_mc_block.seq_no,
+                next_seqno: next.first_mc_block.seq_no,
+            });
+        }
+
+        let expected = self.last_mc_block.seq_no.checked_add(1);
+        if expected != Some(next.first_mc_block.seq_no) {
+            return Err(ArchiveDataError::MasterchainGap {
+                last_seqno: self.last_mc_block.seq_no,
+                next_seqno: next.first_mc_block.seq_no,
+            });
+        }
+
+        // NOTE: shard blocks are stored in the archive of the masterchain block
+        // which commits them, so the first block of each shard in the next archive
+        // must refer to the top block (or its parent/children) of this archive
+        for (id, prev_id) in &next.external_links {
+            if !self.top_blocks.contains(prev_id) {
+                return Err(ArchiveDataError::UnlinkedBlock {
+                    id: id.clone(),
+                    prev_id: prev_id.clone(),
+                });
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Returns ids of all top shard blocks referenced by the masterchain block
+pub fn read_top_shard_blocks(
+    block: &ton_block::Block,
+) -> Result<Vec<ton_block::BlockIdExt>, ArchiveDataError> {
+    let extra = block
+        .read_extra()
+        .map_err(|_| ArchiveDataError::InvalidBlockData)?;
+    let custom = extra
+        .read_custom()
+        .map_err(|_| ArchiveDataError::InvalidBlockData)?
+        .ok_or(ArchiveDataError::InvalidBlockData)?;
+
+    let mut ids = Vec::new();
+    custom
+        .hashes()
+        .iterate_shards(|ident, descr| {
+            ids.push(ton_block::BlockIdExt {
+                shard_id: ident,
+                seq_no: descr.seq_no,
+                root_hash: descr.root_hash,
+                file_hash: descr.file_hash,
+            });
+            Ok(true)
+        })
+        .map_err(|_| ArchiveDataError::InvalidBlockData)?;
+
+    Ok(ids)
+}
+
+/// Returns ids of the previous blocks (two blocks after merge)
+pub fn prev_block_ids(
+    id: &ton_block::BlockIdExt,
+    info: &ton_block::BlockInfo,
+) -> Result<Vec<ton_block::BlockIdExt>, ArchiveDataError> {
+    let make_id =
+        |shard_id: ton_block::ShardIdent, prev: ton_block::ExtBlkRef| ton_block::BlockIdExt {
+            shard_id,
+            seq_no: prev.seq_no,
+            root_hash: prev.root_hash,
+            file_hash: prev.file_hash,
         };
-        Ok((block, block_proof))
+
+    let prev_ref = info
+        .read_prev_ref()
+        .map_err(|_| ArchiveDataError::InvalidBlockData)?;
+    let prev1 = prev_ref
+        .prev1()
+        .map_err(|_| ArchiveDataError::InvalidBlockData)?;
+
+    if info.after_merge() {
+        let prev2 = prev_ref
+            .prev2()
+            .map_err(|_| ArchiveDataError::InvalidBlockData)?
+            .ok_or(ArchiveDataError::InvalidBlockData)?;
+        let (left, right) = id
+            .shard_id
+            .split()
+            .map_err(|_| ArchiveDataError::InvalidBlockData)?;
+        Ok(vec![make_id(left, prev1), make_id(right, prev2)])
+    } else if info.after_split() {
+        let parent = id
+            .shard_id
+            .merge()
+            .map_err(|_| ArchiveDataError::InvalidBlockData)?;
+        Ok(vec![make_id(parent, prev1)])
+    } else {
+        Ok(vec![make_id(id.shard_id, prev1)])
+    }
+}
+
+/// Checks that the shard block master_ref matches the archived masterchain block
+fn check_master_ref(
+    mc_block_ids: &BTreeMap<u32, ton_block::BlockIdExt>,
+    info: &ton_block::BlockInfo,
+) -> Result<(), ArchiveDataError> {
+    let master_ref = info
+        .read_master_ref()
+        .map_err(|_| ArchiveDataError::InvalidBlockData)?
+        .ok_or(ArchiveDataError::MasterRefNotFound)?
+        .master;
+
+    if info.min_ref_mc_seqno() > master_ref.seq_no {
+        return Err(ArchiveDataError::InvalidMinRefMcSeqno {
+            min_ref_mc_seqno: info.min_ref_mc_seqno(),
+            master_seqno: master_ref.seq_no,
+        });
+    }
+
+    if let Some(archived) = mc_block_ids.get(&master_ref.seq_no) {
+        if archived.root_hash != master_ref.root_hash || archived.file_hash != master_ref.file_hash
+        {
+            return Err(ArchiveDataError::MasterRefMismatch {
+                master_ref: ton_block::BlockIdExt {
+                    shard_id: ton_block::ShardIdent::masterchain(),
+                    seq_no: master_ref.seq_no,
+                    root_hash: master_ref.root_hash,
+                    file_hash: master_ref.file_hash,
+                },
+                archived: archived.clone(),
+            });
+        }
+    }
+
+    Ok(())
+}
+
+/// Searches for the entries with the same name
+fn find_duplicate_entries(entries: &[(&str, EntryBytes<'_>)]) -> Vec<EntryConflict> {
+    use std::collections::hash_map;
+
+    let mut conflicts = Vec::new();
+    let mut names = HashMap::with_capacity(entries.len());
+    for (name, data) in entries {
+        match names.entry(*name) {
+            hash_map::Entry::Vacant(entry) => {
+                entry.insert(data.as_slice());
+            }
+            hash_map::Entry::Occupied(entry) => {
+                let name = name.to_string();
+                conflicts.push(if *entry.get() == data.as_slice() {
+                    EntryConflict::Duplicate { name }
+                } else {
+                    EntryConflict::DifferentData { name }
+                });
+            }
+        }
+    }
+    conflicts
+}
+
+/// Conflict between archive entries
+#[derive(Debug, Clone)]
+pub enum EntryConflict {
+    /// Entry with the same name and data appears several times
+    Duplicate { name: String },
+    /// Entries with the same name have different data (e.g. disagreeing proofs)
+    DifferentData { name: String },
+    /// Different blocks with the same shard and seqno
+    Fork {
+        shard_id: ton_block::ShardIdent,
+        seq_no: u32,
+        ids: Vec<ton_block::BlockIdExt>,
+    },
+}
+
+impl EntryConflict {
+    /// Ids of the conflicting blocks
+    pub fn ids(&self) -> &[ton_block::BlockIdExt] {
+        match self {
+            Self::Fork { ids, .. } => ids,
+            _ => &[],
+        }
+    }
+}
+
+impl std::fmt::Display for EntryConflict {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Duplicate { name } => f.write_fmt(format_args!("duplicate entry {name}")),
+            Self::DifferentData { name } => {
+                f.write_fmt(format_args!("entries {name} have different data"))
+            }
+            Self::Fork {
+                shard_id,
+                seq_no,
+                ids,
+            } => {
+                f.write_fmt(format_args!("fork at {shard_id}:{seq_no}:"))?;
+                for id in ids {
+                    f.write_fmt(format_args!(" {id}"))?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+fn format_conflicts(conflicts: &[EntryConflict]) -> String {
+    conflicts
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
+/// Entry data either borrowed from the package or sharing ownership of it
+#[derive(Clone)]
+enum EntryBytes<'a> {
+    Borrowed(&'a [u8]),
+    Shared(Arc<[u8]>, Range<usize>),
+}
+
+impl EntryBytes<'_> {
+    fn as_slice(&self) -> &[u8] {
+        match self {
+            Self::Borrowed(data) => data,
+            Self::Shared(data, range) => &data[range.clone()],
+        }
     }
 }
 
@@ -185,59 +1215,206 @@ pub fn deserialize_block_proof(
     }
 }
 
-fn contains_previous_block(
-    map: &HashMap<ton_block::ShardIdent, BTreeSet<u32>>,
-    shard_ident: &ton_block::ShardIdent,
-    prev_seqno: u32,
-) -> bool {
-    if let Ok((left, right)) = shard_ident.split() {
-        // Check case after merge in the same archive in the left child
-        if let Some(ids) = map.get(&left) {
-            // Search prev seqno in the left shard
-            if ids.contains(&prev_seqno) {
-                return true;
-            }
-        }
+/// Checks the Merkle proof of the block header and returns the proven block info
+pub fn read_proven_block_info(
+    proof: &ton_block::BlockProof,
+) -> Result<ton_block::BlockInfo, ArchiveDataError> {
+    let block_id = &proof.proof_for;
 
-        // Check case after merge in the same archive in the right child
-        if let Some(ids) = map.get(&right) {
-            // Search prev seqno in the right shard
-            if ids.contains(&prev_seqno) {
-                return true;
-            }
-        }
+    if proof.root.cell_type() != ton_types::CellType::MerkleProof {
+        return Err(ArchiveDataError::InvalidMerkleProof);
+    }
+    let merkle_proof = ton_block::MerkleProof::construct_from_cell(proof.root.clone())
+        .map_err(|_| ArchiveDataError::InvalidMerkleProof)?;
+    if merkle_proof.hash != block_id.root_hash {
+        return Err(ArchiveDataError::MerkleProofHashMismatch);
     }
 
-    if let Ok(parent) = shard_ident.merge() {
-        // Check case after second split in the same archive
-        if let Some(ids) = map.get(&parent) {
-            // Search prev shard in the parent shard
-            if ids.contains(&prev_seqno) {
-                return true;
-            }
-        }
+    // Level 0 hash and depth are computed as if all pruned branches were present
+    if merkle_proof.proof.hash(0) != merkle_proof.hash
+        || merkle_proof.proof.depth(0) != merkle_proof.depth
+    {
+        return Err(ArchiveDataError::InvalidMerkleProof);
+    }
+
+    let virt_root = merkle_proof.proof.virtualize(1);
+    if virt_root.repr_hash() != block_id.root_hash {
+        return Err(ArchiveDataError::MerkleProofHashMismatch);
+    }
+
+    let virt_block = ton_block::Block::construct_from_cell(virt_root)
+        .map_err(|_| ArchiveDataError::InvalidMerkleProof)?;
+    let info = virt_block
+        .read_info()
+        .map_err(|_| ArchiveDataError::InvalidMerkleProof)?;
+
+    if info.shard() != &block_id.shard_id {
+        return Err(ArchiveDataError::ProvenHeaderMismatch { field: "shard" });
+    }
+    if info.seq_no() != block_id.seq_no {
+        return Err(ArchiveDataError::ProvenHeaderMismatch { field: "seq_no" });
     }
 
-    false
+    Ok(info)
+}
+
+/// Compares the block info with the one from the Merkle proof
+fn compare_block_info(
+    info: &ton_block::BlockInfo,
+    proven_info: &ton_block::BlockInfo,
+) -> Result<(), ArchiveDataError> {
+    let fields = [
+        ("gen_utime", info.gen_utime() == proven_info.gen_utime()),
+        ("start_lt", info.start_lt() == proven_info.start_lt()),
+        ("end_lt", info.end_lt() == proven_info.end_lt()),
+        ("key_block", info.key_block() == proven_info.key_block()),
+        (
+            "after_merge",
+            info.after_merge() == proven_info.after_merge(),
+        ),
+        (
+            "after_split",
+            info.after_split() == proven_info.after_split(),
+        ),
+        (
+            "before_split",
+            info.before_split() == proven_info.before_split(),
+        ),
+        (
+            "vert_seq_no",
+            info.vert_seq_no() == proven_info.vert_seq_no(),
+        ),
+        (
+            "prev_key_block_seqno",
+            info.prev_key_block_seqno() == proven_info.prev_key_block_seqno(),
+        ),
+        (
+            "gen_catchain_seqno",
+            info.gen_catchain_seqno() == proven_info.gen_catchain_seqno(),
+        ),
+        (
+            "min_ref_mc_seqno",
+            info.min_ref_mc_seqno() == proven_info.min_ref_mc_seqno(),
+        ),
+        (
+            "prev_ref",
+            info.read_prev_ref().ok() == proven_info.read_prev_ref().ok(),
+        ),
+        (
+            "master_ref",
+            info.read_master_ref().ok() == proven_info.read_master_ref().ok(),
+        ),
+    ];
+
+    match fields.into_iter().find(|(_, equal)| !equal) {
+        Some((field, _)) => Err(ArchiveDataError::ProvenHeaderMismatch { field }),
+        None => Ok(()),
+    }
+}
+
+/// Whether the shards are in the same workchain and one of them contains another
+fn intersects(left: &ton_block::ShardIdent, right: &ton_block::ShardIdent) -> bool {
+    left.workchain_id() == right.workchain_id() && left.intersect_with(right)
 }
 
-type WithData<'a, T> = (T, &'a [u8]);
 type RefWithData<'a, T> = (&'a T, &'a [u8]);
 
+type BlockInfos<'a> = BTreeMap<&'a ton_block::BlockIdExt, ton_block::BlockInfo>;
+
+type BlocksByShard<'a> = HashMap<ton_block::ShardIdent, BTreeMap<u32, &'a ton_block::BlockIdExt>>;
+
+// Owned archive data must be usable from caches and other threads
+const _: fn() = || {
+    fn assert_send_sync<T: Send + Sync + 'static>() {}
+    assert_send_sync::<OwnedArchiveData>();
+};
+
 #[derive(thiserror::Error, Debug)]
 pub enum ArchiveDataError {
     #[error("Invalid package")]
     InvalidPackage(#[from] ArchivePackageError),
     #[error("Invalid package entry id")]
     InvalidPackageEntryId(#[from] PackageEntryIdError),
+    #[error("Conflicting entries: {}", format_conflicts(.0))]
+    ConflictingEntries(Vec<EntryConflict>),
     #[error("Empty archive")]
     EmptyArchive,
-    #[error("Inconsistent masterchain blocks")]
-    InconsistentMasterchainBlocks,
-    #[error("Inconsistent masterchain block {shard_ident}:{seqno}")]
-    InconsistentShardchainBlock {
-        shard_ident: ton_block::ShardIdent,
-        seqno: u32,
+    #[error("Too many masterchain blocks in archive: {count} (max {max})")]
+    TooManyMasterchainBlocks { count: usize, max: usize },
+    #[error("Archive starts at masterchain block {first_seqno} which is not aligned to {slice_size} blocks after key block {key_block_seqno}")]
+    UnalignedArchive {
+        first_seqno: u32,
+        key_block_seqno: u32,
+        slice_size: u32,
+    },
+    #[error("Archive masterchain blocks {first_seqno}..={last_seqno} cross the slice boundary of {slice_size} blocks after key block {key_block_seqno}")]
+    ArchiveCrossesSliceBoundary {
+        first_seqno: u32,
+        last_seqno: u32,
+        key_block_seqno: u32,
+        slice_size: u32,
+    },
+    #[error("Key block {seqno} must be the first masterchain block in archive")]
+    UnexpectedKeyBlock { seqno: u32 },
+    #[error("Masterchain blocks {first}..={last} not found in archive")]
+    MissingMasterchainBlocks { first: u32, last: u32 },
+    #[error("Masterchain gap between archives: last seqno {last_seqno}, next seqno {next_seqno}")]
+    MasterchainGap { last_seqno: u32, next_seqno: u32 },
+    #[error("Next archive starts at masterchain block {next_seqno} which is already in the previous archive ending at {last_seqno}")]
+    OverlappingArchives { last_seqno: u32, next_seqno: u32 },
+    #[error("Block {id} refers to the unknown previous block {prev_id}")]
+    UnlinkedBlock {
+        id: ton_block::BlockIdExt,
+        prev_id: ton_block::BlockIdExt,
+    },
+    #[error("Referenced shard block {expected} doesn't match the archived block {found}")]
+    ShardBlockHashMismatch {
+        expected: ton_block::BlockIdExt,
+        found: ton_block::BlockIdExt,
+    },
+    #[error("Referenced shard block {id} not found in archive")]
+    MissingShardBlock { id: ton_block::BlockIdExt },
+    #[error("Shard block {id} is not committed by any masterchain block")]
+    UncommittedShardBlock { id: ton_block::BlockIdExt },
+    #[error("Shard block has no master_ref")]
+    MasterRefNotFound,
+    #[error("Shard block master_ref {master_ref} doesn't match the archived block {archived}")]
+    MasterRefMismatch {
+        master_ref: ton_block::BlockIdExt,
+        archived: ton_block::BlockIdExt,
+    },
+    #[error("min_ref_mc_seqno {min_ref_mc_seqno} exceeds master_ref seqno {master_seqno}")]
+    InvalidMinRefMcSeqno {
+        min_ref_mc_seqno: u32,
+        master_seqno: u32,
+    },
+    #[error("Previous block {expected} doesn't match the archived block {found}")]
+    PrevBlockHashMismatch {
+        expected: ton_block::BlockIdExt,
+        found: ton_block::BlockIdExt,
+    },
+    #[error("Block start_lt {start_lt} exceeds end_lt {end_lt}")]
+    InvalidLtRange { start_lt: u64, end_lt: u64 },
+    #[error("Block start_lt {start_lt} doesn't exceed end_lt {prev_end_lt} of the previous block {prev_id}")]
+    LtOverlap {
+        prev_id: ton_block::BlockIdExt,
+        prev_end_lt: u64,
+        start_lt: u64,
+    },
+    #[error("Block gen_utime {gen_utime} is less than gen_utime {prev_gen_utime} of the previous block {prev_id}")]
+    GenUtimeDecreased {
+        prev_id: ton_block::BlockIdExt,
+        prev_gen_utime: u32,
+        gen_utime: u32,
+    },
+    #[error("Block start_lt {start_lt} doesn't exceed master_ref end_lt {master_end_lt}")]
+    MasterRefLtOverlap { master_end_lt: u64, start_lt: u64 },
+    #[error("master_ref end_lt {found} doesn't match the masterchain block end_lt {expected}")]
+    MasterRefLtMismatch { expected: u64, found: u64 },
+    #[error("Block gen_utime differs from {other_id} by {gap} seconds")]
+    SuspiciousGenUtimeGap {
+        other_id: ton_block::BlockIdExt,
+        gap: u32,
     },
     #[error("Block not found in archive")]
     BlockDataNotFound,
@@ -255,4 +1432,26 @@ pub enum ArchiveDataError {
     ProofForAnotherBlock,
     #[error("Proof for non-masterchain block")]
     ProofForNonMasterchainBlock,
+    #[error("Invalid Merkle proof")]
+    InvalidMerkleProof,
+    #[error("Merkle proof hash doesn't match the block root hash")]
+    MerkleProofHashMismatch,
+    #[error("Proven block header field {field} doesn't match the block")]
+    ProvenHeaderMismatch { field: &'static str },
+    #[error("Not a key block")]
+    NotAKeyBlock,
+    #[error("Invalid key block config")]
+    InvalidKeyBlockConfig,
+    #[error("Key block {seqno} not found")]
+    KeyBlockNotFound { seqno: u32 },
+    #[error("Block signatures not found in proof")]
+    BlockSignaturesNotFound,
+    #[error("Catchain seqno mismatch: expected {expected}, found {found}")]
+    CatchainSeqnoMismatch { expected: u32, found: u32 },
+    #[error("Validator list hash mismatch: expected {expected:08x}, found {found:08x}")]
+    ValidatorListHashMismatch { expected: u32, found: u32 },
+    #[error("Invalid block signatures")]
+    InvalidSignatures,
+    #[error("Not enough signatures: weight {weight} of {total_weight}")]
+    NotEnoughSignatures { weight: u64, total_weight: u64 },
 }
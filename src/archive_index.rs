@@ -0,0 +1,348 @@
+use std::io::Write;
+
+use crate::archive_package::*;
+use crate::package_entry_id::*;
+
+/// Sorted list of package entries with their positions in the package
+pub struct ArchiveIndex {
+    entries: Vec<ArchiveIndexEntry>,
+}
+
+impl ArchiveIndex {
+    /// Scans the whole package and collects positions of all entries
+    pub fn build(data: &[u8]) -> Result<Self, ArchiveIndexError> {
+        let mut reader = ArchivePackageViewReader::new(data)?;
+
+        let mut entries = Vec::new();
+        while let Some(entry) = reader.read_next()? {
+            entries.push(ArchiveIndexEntry {
+                id: PackageEntryId::from_filename(entry.name)?,
+                offset: entry.header_offset,
+                len: entry.frame_len,
+            });
+        }
+        entries.sort_unstable_by_key(ArchiveIndexEntry::sort_key);
+
+        Ok(Self { entries })
+    }
+
+    /// Whether the data starts like an index sidecar
+    pub fn is_index(data: &[u8]) -> bool {
+        data.starts_with(&ARCHIVE_INDEX_PREFIX)
+    }
+
+    /// Parses index sidecar
+    pub fn from_slice(mut data: &[u8]) -> Result<Self, ArchiveIndexError> {
+        if read_bytes::<4>(&mut data)? != ARCHIVE_INDEX_PREFIX {
+            return Err(ArchiveIndexError::InvalidIndexHeader);
+        }
+
+        let count = u32::from_le_bytes(read_bytes(&mut data)?) as usize;
+        if data.len() != count.saturating_mul(INDEX_ENTRY_LEN) {
+            return Err(ArchiveIndexError::UnexpectedIndexEof);
+        }
+
+        let mut entries = Vec::with_capacity(count);
+        for _ in 0..count {
+            let [kind] = read_bytes(&mut data)?;
+            let workchain_id = i32::from_le_bytes(read_bytes(&mut data)?);
+            let shard_prefix_tagged = u64::from_le_bytes(read_bytes(&mut data)?);
+            let seq_no = u32::from_le_bytes(read_bytes(&mut data)?);
+            let root_hash = ton_types::UInt256::from(read_bytes::<32>(&mut data)?);
+            let file_hash = ton_types::UInt256::from(read_bytes::<32>(&mut data)?);
+            let offset = usize::try_from(u64::from_le_bytes(read_bytes(&mut data)?))
+                .map_err(|_| ArchiveIndexError::TooLargeEntry)?;
+            let len = u32::from_le_bytes(read_bytes(&mut data)?) as usize;
+
+            let shard_id =
+                ton_block::ShardIdent::with_tagged_prefix(workchain_id, shard_prefix_tagged)
+                    .map_err(|_| ArchiveIndexError::InvalidShardIdent)?;
+            let block_id = ton_block::BlockIdExt {
+                shard_id,
+                seq_no,
+                root_hash,
+                file_hash,
+            };
+
+            let id = match kind {
+                INDEX_ENTRY_BLOCK => PackageEntryId::Block(block_id),
+                INDEX_ENTRY_PROOF => PackageEntryId::Proof(block_id),
+                INDEX_ENTRY_PROOF_LINK => PackageEntryId::ProofLink(block_id),
+                _ => return Err(ArchiveIndexError::InvalidEntryKind),
+            };
+
+            entries.push(ArchiveIndexEntry { id, offset, len });
+        }
+
+        if !entries
+            .windows(2)
+            .all(|pair| pair[0].sort_key() <= pair[1].sort_key())
+        {
+            return Err(ArchiveIndexError::UnsortedIndex);
+        }
+
+        Ok(Self { entries })
+    }
+
+    /// Writes index sidecar
+    pub fn write_to<W: Write>(&self, mut writer: W) -> Result<(), ArchiveIndexError> {
+        let count =
+            u32::try_from(self.entries.len()).map_err(|_| ArchiveIndexError::TooManyEntries)?;
+
+        writer.write_all(&ARCHIVE_INDEX_PREFIX)?;
+        writer.write_all(&count.to_le_bytes())?;
+
+        for entry in &self.entries {
+            let block_id = entry.id.block_id();
+            let len = u32::try_from(entry.len).map_err(|_| ArchiveIndexError::TooLargeEntry)?;
+
+            writer.write_all(&[entry.kind()])?;
+            writer.write_all(&block_id.shard_id.workchain_id().to_le_bytes())?;
+            writer.write_all(&block_id.shard_id.shard_prefix_with_tag().to_le_bytes())?;
+            writer.write_all(&block_id.seq_no.to_le_bytes())?;
+            writer.write_all(block_id.root_hash.as_slice())?;
+            writer.write_all(block_id.file_hash.as_slice())?;
+            writer.write_all(&(entry.offset as u64).to_le_bytes())?;
+            writer.write_all(&len.to_le_bytes())?;
+        }
+
+        writer.flush()?;
+        Ok(())
+    }
+
+    pub fn entries(&self) -> &[ArchiveIndexEntry] {
+        &self.entries
+    }
+
+    /// Searches the entry with the exact id
+    pub fn get(&self, id: &PackageEntryId<ton_block::BlockIdExt>) -> Option<&ArchiveIndexEntry> {
+        let block_id = id.block_id();
+        self.find(&block_id.shard_id, block_id.seq_no)
+            .iter()
+            .find(|entry| &entry.id == id)
+    }
+
+    /// Returns all entries (block, proof, prooflink) for the specified shard and seqno
+    pub fn find(&self, shard_id: &ton_block::ShardIdent, seq_no: u32) -> &[ArchiveIndexEntry] {
+        let key = (
+            shard_id.workchain_id(),
+            shard_id.shard_prefix_with_tag(),
+            seq_no,
+        );
+
+        let start = self.entries.partition_point(|entry| {
+            let (workchain_id, shard, seq_no, _) = entry.sort_key();
+            (workchain_id, shard, seq_no) < key
+        });
+        let end = self.entries.partition_point(|entry| {
+            let (workchain_id, shard, seq_no, _) = entry.sort_key();
+            (workchain_id, shard, seq_no) <= key
+        });
+
+        &self.entries[start..end]
+    }
+
+    /// Reads block entry with the specified id from the package
+    pub fn get_block<'a>(
+        &self,
+        data: &'a [u8],
+        block_id: &ton_block::BlockIdExt,
+    ) -> Result<Option<ArchivePackageEntryView<'a>>, ArchiveIndexError> {
+        match self.get(&PackageEntryId::Block(block_id.clone())) {
+            Some(entry) => entry.read(data).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Reads proof or prooflink entry with the specified id from the package
+    pub fn get_proof<'a>(
+        &self,
+        data: &'a [u8],
+        block_id: &ton_block::BlockIdExt,
+    ) -> Result<Option<ArchivePackageEntryView<'a>>, ArchiveIndexError> {
+        let id = if block_id.shard_id.is_masterchain() {
+            PackageEntryId::Proof(block_id.clone())
+        } else {
+            PackageEntryId::ProofLink(block_id.clone())
+        };
+
+        match self.get(&id) {
+            Some(entry) => entry.read(data).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Reads block entry with the specified shard and seqno from the package
+    pub fn get_block_by_seqno<'a>(
+        &self,
+        data: &'a [u8],
+        shard_id: &ton_block::ShardIdent,
+        seq_no: u32,
+    ) -> Result<Option<ArchivePackageEntryView<'a>>, ArchiveIndexError> {
+        let entry = self
+            .find(shard_id, seq_no)
+            .iter()
+            .find(|entry| matches!(entry.id, PackageEntryId::Block(_)));
+
+        match entry {
+            Some(entry) => entry.read(data).map(Some),
+            None => Ok(None),
+        }
+    }
+}
+
+pub struct ArchiveIndexEntry {
+    pub id: PackageEntryId<ton_block::BlockIdExt>,
+    /// Offset of the entry header in the package
+    pub offset: usize,
+    /// Full entry length including header and name
+    pub len: usize,
+}
+
+impl ArchiveIndexEntry {
+    /// Reads and verifies the entry from the package
+    pub fn read<'a>(
+        &self,
+        data: &'a [u8],
+    ) -> Result<ArchivePackageEntryView<'a>, ArchiveIndexError> {
+        let entry = ArchivePackageEntryView::read_at(data, self.offset)?;
+        if entry.frame_len != self.len || entry.name != self.id.filename() {
+            return Err(ArchiveIndexError::EntryMismatch);
+        }
+        Ok(entry)
+    }
+
+    fn kind(&self) -> u8 {
+        entry_kind(&self.id)
+    }
+
+    fn sort_key(&self) -> SortKey {
+        make_sort_key(self.id.block_id(), self.kind())
+    }
+}
+
+type SortKey = (i32, u64, u32, u8);
+
+fn make_sort_key(block_id: &ton_block::BlockIdExt, kind: u8) -> SortKey {
+    (
+        block_id.shard_id.workchain_id(),
+        block_id.shard_id.shard_prefix_with_tag(),
+        block_id.seq_no,
+        kind,
+    )
+}
+
+fn entry_kind(id: &PackageEntryId<ton_block::BlockIdExt>) -> u8 {
+    match id {
+        PackageEntryId::Block(_) => INDEX_ENTRY_BLOCK,
+        PackageEntryId::Proof(_) => INDEX_ENTRY_PROOF,
+        PackageEntryId::ProofLink(_) => INDEX_ENTRY_PROOF_LINK,
+    }
+}
+
+fn read_bytes<const N: usize>(data: &mut &[u8]) -> Result<[u8; N], ArchiveIndexError> {
+    if data.len() < N {
+        return Err(ArchiveIndexError::UnexpectedIndexEof);
+    }
+    let (head, tail) = data.split_at(N);
+    *data = tail;
+
+    let mut result = [0; N];
+    result.copy_from_slice(head);
+    Ok(result)
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum ArchiveIndexError {
+    #[error("Invalid package")]
+    InvalidPackage(#[from] ArchivePackageError),
+    #[error("Invalid package entry id")]
+    InvalidPackageEntryId(#[from] PackageEntryIdError),
+    #[error("Invalid index header")]
+    InvalidIndexHeader,
+    #[error("Unexpected index eof")]
+    UnexpectedIndexEof,
+    #[error("Invalid index entry kind")]
+    InvalidEntryKind,
+    #[error("Invalid shard ident")]
+    InvalidShardIdent,
+    #[error("Index entries are not sorted")]
+    UnsortedIndex,
+    #[error("Too many index entries")]
+    TooManyEntries,
+    #[error("Too large package entry")]
+    TooLargeEntry,
+    #[error("Package entry doesn't match the index")]
+    EntryMismatch,
+    #[error("IO error")]
+    Io(#[from] std::io::Error),
+}
+
+const ARCHIVE_INDEX_PREFIX: [u8; 4] = u32::to_le_bytes(0xae8fdd1d);
+
+/// kind + workchain + shard + seqno + root hash + file hash + offset + len
+const INDEX_ENTRY_LEN: usize = 1 + 4 + 8 + 4 + 32 + 32 + 8 + 4;
+
+const INDEX_ENTRY_BLOCK: u8 = 0;
+const INDEX_ENTRY_PROOF: u8 = 1;
+const INDEX_ENTRY_PROOF_LINK: u8 = 2;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn block_id(seq_no: u32, hash: u8) -> ton_block::BlockIdExt {
+        ton_block::BlockIdExt {
+            shard_id: ton_block::ShardIdent::masterchain(),
+            seq_no,
+            root_hash: ton_types::UInt256::from([hash; 32]),
+            file_hash: ton_types::UInt256::from([hash + 1; 32]),
+        }
+    }
+
+    #[test]
+    fn index_round_trip() {
+        let ids = (1..=3)
+            .flat_map(|seq_no| {
+                let id = block_id(seq_no, seq_no as u8 * 2);
+                [PackageEntryId::Block(id.clone()), PackageEntryId::Proof(id)]
+            })
+            .collect::<Vec<_>>();
+
+        // NOTE: entries are written in reverse order to check sorting
+        let mut writer = ArchivePackageWriter::new(Vec::new()).unwrap();
+        for (i, id) in ids.iter().enumerate().rev() {
+            writer
+                .write_entry(&id.filename(), &vec![i as u8; i + 1])
+                .unwrap();
+        }
+        let package = writer.finish().unwrap();
+
+        let index = ArchiveIndex::build(&package).unwrap();
+        let mut sidecar = Vec::new();
+        index.write_to(&mut sidecar).unwrap();
+        assert_eq!(sidecar.len(), 8 + ids.len() * INDEX_ENTRY_LEN);
+        assert!(ArchiveIndex::is_index(&sidecar));
+        assert!(!ArchiveIndex::is_index(&package));
+
+        let index = ArchiveIndex::from_slice(&sidecar).unwrap();
+        assert_eq!(index.entries().len(), ids.len());
+
+        for (i, id) in ids.iter().enumerate() {
+            let entry = index.get(id).unwrap().read(&package).unwrap();
+            assert_eq!(entry.name, id.filename());
+            assert_eq!(entry.data, vec![i as u8; i + 1].as_slice());
+        }
+
+        // Same seqno, but different hashes
+        let unknown = PackageEntryId::Block(block_id(2, 100));
+        assert!(index.get(&unknown).is_none());
+        assert_eq!(index.find(&unknown.block_id().shard_id, 2).len(), 2);
+
+        let block = index
+            .get_block_by_seqno(&package, &ton_block::ShardIdent::masterchain(), 3)
+            .unwrap()
+            .unwrap();
+        assert_eq!(block.name, ids[4].filename());
+    }
+}
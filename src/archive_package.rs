@@ -1,32 +1,357 @@
+use std::io::{Read, Write};
+use std::ops::Range;
+
+use crate::package_entry_id::*;
+
 pub struct ArchivePackageViewReader<'a> {
     data: &'a [u8],
     offset: usize,
+    strict: bool,
 }
 
 impl<'a> ArchivePackageViewReader<'a> {
     pub fn new(data: &'a [u8]) -> Result<Self, ArchivePackageError> {
         let mut offset = 0;
         read_package_header(data, &mut offset)?;
-        Ok(Self { data, offset })
+        Ok(Self {
+            data,
+            offset,
+            strict: false,
+        })
+    }
+
+    /// Fails on truncated entry headers and trailing bytes instead of
+    /// treating them as the end of the package
+    pub fn with_strict_eof(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
     }
 
     pub fn read_next(
         &mut self,
     ) -> Result<Option<ArchivePackageEntryView<'a>>, ArchivePackageError> {
-        ArchivePackageEntryView::read_from_view(self.data, &mut self.offset)
+        let mut offset = self.offset;
+        let entry = self.read_entry(&mut offset)?;
+        self.offset = offset;
+        Ok(entry)
+    }
+
+    /// Reads the next entry without advancing the reader
+    pub fn peek(&self) -> Result<Option<ArchivePackageEntryView<'a>>, ArchivePackageError> {
+        let mut offset = self.offset;
+        self.read_entry(&mut offset)
+    }
+
+    /// Current position in the package
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// Moves the reader to the specified offset. It must point to the entry header
+    pub fn seek_to(&mut self, offset: usize) -> Result<(), ArchivePackageError> {
+        if offset < ARCHIVE_PREFIX.len() || offset > self.data.len() {
+            return Err(ArchivePackageError::OffsetOutOfBounds { offset });
+        }
+        self.offset = offset;
+        Ok(())
+    }
+
+    /// Counts remaining entries without validating names and touching entry data
+    pub fn count_entries(&self) -> Result<usize, ArchivePackageError> {
+        let mut offset = self.offset;
+        let mut count = 0;
+
+        loop {
+            let header = match offset
+                .checked_add(ENTRY_HEADER_LEN)
+                .and_then(|header_end| self.data.get(offset..header_end))
+            {
+                Some(header) => header,
+                None => break,
+            };
+
+            let (filename_size, data_size) = parse_entry_header(header)?;
+            let (_, frame_len) = entry_layout(offset, filename_size, data_size)?;
+            if self.data.len() - offset < frame_len {
+                return Err(ArchivePackageError::UnexpectedEntryEof);
+            }
+
+            offset += frame_len;
+            count += 1;
+        }
+
+        if self.strict {
+            check_package_tail(&self.data[offset..], offset as u64)?;
+        }
+        Ok(count)
+    }
+
+    fn read_entry(
+        &self,
+        offset: &mut usize,
+    ) -> Result<Option<ArchivePackageEntryView<'a>>, ArchivePackageError> {
+        let entry = ArchivePackageEntryView::read_from_view(self.data, offset)?;
+        if entry.is_none() && self.strict {
+            check_package_tail(&self.data[*offset..], *offset as u64)?;
+        }
+        Ok(entry)
+    }
+}
+
+impl<'a> Iterator for ArchivePackageViewReader<'a> {
+    type Item = Result<ArchivePackageEntryView<'a>, ArchivePackageError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.read_next() {
+            Ok(entry) => entry.map(Ok),
+            Err(e) => {
+                // Stop iteration after the first error
+                self.offset = self.data.len();
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+impl std::iter::FusedIterator for ArchivePackageViewReader<'_> {}
+
+pub struct ArchivePackageStreamReader<R> {
+    reader: R,
+    state: StreamReaderState,
+}
+
+impl<R: Read> ArchivePackageStreamReader<R> {
+    pub fn new(mut reader: R) -> Result<Self, ArchivePackageError> {
+        let mut header = [0; 4];
+        read_exact_or(
+            &mut reader,
+            &mut header,
+            ArchivePackageError::UnexpectedArchiveEof,
+        )?;
+        Ok(Self {
+            reader,
+            state: StreamReaderState::new(&header)?,
+        })
+    }
+
+    /// Fails on truncated entry headers and trailing bytes instead of
+    /// treating them as the end of the package
+    pub fn with_strict_eof(mut self, strict: bool) -> Self {
+        self.state.strict = strict;
+        self
+    }
+
+    pub fn read_next(&mut self) -> Result<Option<ArchivePackageEntry>, ArchivePackageError> {
+        let mut header = [0; ENTRY_HEADER_LEN];
+        let filled = read_full(&mut self.reader, &mut header)?;
+        let (filename_size, data_size) = match self.state.parse_header(&header[..filled])? {
+            Some(sizes) => sizes,
+            None => return Ok(None),
+        };
+
+        let mut name = vec![0; filename_size];
+        read_exact_or(
+            &mut self.reader,
+            &mut name,
+            ArchivePackageError::UnexpectedEntryEof,
+        )?;
+        let name = parse_entry_name(name)?;
+
+        // NOTE: data is read incrementally to not allocate the whole buffer
+        // before the reader actually has it
+        let mut data = Vec::new();
+        (&mut self.reader)
+            .take(data_size as u64)
+            .read_to_end(&mut data)?;
+
+        self.state.make_entry(name, data, data_size).map(Some)
+    }
+
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+}
+
+/// Position and mode of the stream reader. Shared by the sync and async readers
+pub(crate) struct StreamReaderState {
+    /// NOTE: streams can be larger than the address space, so offsets are not `usize`
+    offset: u64,
+    pub(crate) strict: bool,
+}
+
+impl StreamReaderState {
+    /// Checks the package prefix
+    pub(crate) fn new(header: &[u8]) -> Result<Self, ArchivePackageError> {
+        if header != ARCHIVE_PREFIX {
+            return Err(ArchivePackageError::InvalidArchiveHeader);
+        }
+        Ok(Self {
+            offset: ARCHIVE_PREFIX.len() as u64,
+            strict: false,
+        })
+    }
+
+    /// Parses the entry header. Returns `None` if the stream ended before the full header
+    pub(crate) fn parse_header(
+        &self,
+        header: &[u8],
+    ) -> Result<Option<(usize, usize)>, ArchivePackageError> {
+        if header.len() < ENTRY_HEADER_LEN {
+            if self.strict {
+                check_package_tail(header, self.offset)?;
+            }
+            return Ok(None);
+        }
+        parse_entry_header(header).map(Some)
+    }
+
+    /// Builds the entry from the name and data read after its header
+    /// and moves to the next entry
+    pub(crate) fn make_entry(
+        &mut self,
+        name: String,
+        data: Vec<u8>,
+        data_size: usize,
+    ) -> Result<ArchivePackageEntry, ArchivePackageError> {
+        if data.len() != data_size {
+            return Err(ArchivePackageError::UnexpectedEntryEof);
+        }
+
+        let header_offset = self.offset;
+        let (data_start, frame_len) = entry_layout(0, name.len(), data_size)?;
+        self.offset = header_offset
+            .checked_add(frame_len as u64)
+            .ok_or(ArchivePackageError::OffsetOverflow)?;
+
+        Ok(ArchivePackageEntry {
+            name,
+            data,
+            header_offset,
+            data_offset: header_offset + data_start as u64,
+            frame_len,
+        })
+    }
+}
+
+pub(crate) fn parse_entry_name(name: Vec<u8>) -> Result<String, ArchivePackageError> {
+    String::from_utf8(name).map_err(|_| ArchivePackageError::InvalidArchiveEntryName)
+}
+
+/// Reads until the buffer is full or the reader is exhausted.
+/// Returns the number of bytes read
+pub(crate) fn read_full<R: Read>(reader: &mut R, buf: &mut [u8]) -> std::io::Result<usize> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match reader.read(&mut buf[filled..]) {
+            Ok(0) => break,
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(filled)
+}
+
+fn read_exact_or<R: Read>(
+    reader: &mut R,
+    buf: &mut [u8],
+    eof_error: ArchivePackageError,
+) -> Result<(), ArchivePackageError> {
+    reader
+        .read_exact(buf)
+        .map_err(|e| map_eof_error(e, eof_error))
+}
+
+/// Replaces unexpected eof with the specified error
+pub(crate) fn map_eof_error(
+    e: std::io::Error,
+    eof_error: ArchivePackageError,
+) -> ArchivePackageError {
+    if e.kind() == std::io::ErrorKind::UnexpectedEof {
+        eof_error
+    } else {
+        e.into()
+    }
+}
+
+/// Package reader which skips corrupted parts of the package
+pub struct ArchivePackageSalvageReader<'a> {
+    data: &'a [u8],
+    offset: usize,
+    corrupted: Vec<Range<usize>>,
+}
+
+impl<'a> ArchivePackageSalvageReader<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        let mut offset = 0;
+        if read_package_header(data, &mut offset).is_err() {
+            // NOTE: corrupted header will be reported on the first resync
+            offset = 0;
+        }
+
+        Self {
+            data,
+            offset,
+            corrupted: Vec::new(),
+        }
+    }
+
+    /// Reads the next valid entry, skipping all bytes which can't be parsed
+    pub fn read_next(&mut self) -> Option<ArchivePackageEntryView<'a>> {
+        if self.offset >= self.data.len() {
+            return None;
+        }
+
+        if let Some(entry) = self.read_valid_entry(self.offset) {
+            self.offset += entry.frame_len;
+            return Some(entry);
+        }
+
+        // Search for the next entry prefix with a valid header
+        let start = self.offset;
+        let mut candidate = start + 1;
+        while let Some(pos) = self.data.get(candidate..).and_then(|tail| {
+            tail.windows(ARCHIVE_ENTRY_PREFIX.len())
+                .position(|prefix| prefix == ARCHIVE_ENTRY_PREFIX)
+        }) {
+            candidate += pos;
+            if let Some(entry) = self.read_valid_entry(candidate) {
+                self.corrupted.push(start..candidate);
+                self.offset = candidate + entry.frame_len;
+                return Some(entry);
+            }
+            candidate += 1;
+        }
+
+        self.corrupted.push(start..self.data.len());
+        self.offset = self.data.len();
+        None
+    }
+
+    /// Byte ranges which were skipped so far
+    pub fn corrupted_ranges(&self) -> &[Range<usize>] {
+        &self.corrupted
+    }
+
+    fn read_valid_entry(&self, offset: usize) -> Option<ArchivePackageEntryView<'a>> {
+        let entry = ArchivePackageEntryView::read_at(self.data, offset).ok()?;
+        PackageEntryId::from_filename(entry.name).ok()?;
+        Some(entry)
     }
 }
 
 pub fn read_package_header(buf: &[u8], offset: &mut usize) -> Result<(), ArchivePackageError> {
     let end = *offset;
 
-    // NOTE: `end > end + 4` is needed here because it eliminates useless
-    // bounds check with panic. It is not even included into result assembly
-    if buf.len() < end + 4 || end > end + 4 {
-        return Err(ArchivePackageError::UnexpectedArchiveEof);
-    }
+    let header = match end
+        .checked_add(ARCHIVE_PREFIX.len())
+        .and_then(|header_end| buf.get(end..header_end))
+    {
+        Some(header) => header,
+        None => return Err(ArchivePackageError::UnexpectedArchiveEof),
+    };
 
-    if buf[end..end + 4] == ARCHIVE_PREFIX {
+    if header == ARCHIVE_PREFIX {
         *offset += 4;
         Ok(())
     } else {
@@ -34,49 +359,161 @@ pub fn read_package_header(buf: &[u8], offset: &mut usize) -> Result<(), Archive
     }
 }
 
+pub struct ArchivePackageWriter<W> {
+    writer: W,
+}
+
+impl<W: Write> ArchivePackageWriter<W> {
+    pub fn new(mut writer: W) -> Result<Self, ArchivePackageError> {
+        write_package_header(&mut writer)?;
+        Ok(Self { writer })
+    }
+
+    pub fn write_entry(&mut self, name: &str, data: &[u8]) -> Result<(), ArchivePackageError> {
+        let filename_size =
+            u16::try_from(name.len()).map_err(|_| ArchivePackageError::TooLongEntryName)?;
+        let data_size =
+            u32::try_from(data.len()).map_err(|_| ArchivePackageError::TooLargeEntryData)?;
+
+        self.writer.write_all(&ARCHIVE_ENTRY_PREFIX)?;
+        self.writer.write_all(&filename_size.to_le_bytes())?;
+        self.writer.write_all(&data_size.to_le_bytes())?;
+        self.writer.write_all(name.as_bytes())?;
+        self.writer.write_all(data)?;
+        Ok(())
+    }
+
+    pub fn write_entry_view(
+        &mut self,
+        entry: &ArchivePackageEntryView<'_>,
+    ) -> Result<(), ArchivePackageError> {
+        self.write_entry(entry.name, entry.data)
+    }
+
+    pub fn finish(mut self) -> Result<W, ArchivePackageError> {
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+pub fn write_package_header<W: Write>(writer: &mut W) -> Result<(), ArchivePackageError> {
+    writer.write_all(&ARCHIVE_PREFIX)?;
+    Ok(())
+}
+
+/// Owned package entry read from the stream
+pub struct ArchivePackageEntry {
+    pub name: String,
+    pub data: Vec<u8>,
+    /// Offset of the entry prefix from the beginning of the stream
+    pub header_offset: u64,
+    /// Offset of the entry data from the beginning of the stream
+    pub data_offset: u64,
+    /// Full entry length including header and name
+    pub frame_len: usize,
+}
+
 pub struct ArchivePackageEntryView<'a> {
     pub name: &'a str,
     pub data: &'a [u8],
+    /// Offset of the entry prefix from the beginning of the package
+    pub header_offset: usize,
+    /// Offset of the entry data from the beginning of the package
+    pub data_offset: usize,
+    /// Full entry length including header and name
+    pub frame_len: usize,
 }
 
 impl<'a> ArchivePackageEntryView<'a> {
+    /// Reads an entry which starts at the specified offset
+    pub fn read_at(buf: &'a [u8], offset: usize) -> Result<Self, ArchivePackageError> {
+        let mut offset = offset;
+        Self::read_from_view(buf, &mut offset)?.ok_or(ArchivePackageError::UnexpectedEntryEof)
+    }
+
     fn read_from_view(
         buf: &'a [u8],
         offset: &mut usize,
     ) -> Result<Option<Self>, ArchivePackageError> {
-        if buf.len() < *offset + 8 {
-            return Ok(None);
-        }
+        let header_offset = *offset;
+        let header = match header_offset
+            .checked_add(ENTRY_HEADER_LEN)
+            .and_then(|header_end| buf.get(header_offset..header_end))
+        {
+            Some(header) => header,
+            None => return Ok(None),
+        };
 
-        if buf[*offset..*offset + 2] != ARCHIVE_ENTRY_PREFIX {
-            return Err(ArchivePackageError::InvalidArchiveEntryHeader);
-        }
-        *offset += 2;
+        let (filename_size, data_size) = parse_entry_header(header)?;
 
-        let filename_size = u16::from_le_bytes([buf[*offset], buf[*offset + 1]]) as usize;
-        *offset += 2;
+        let name_offset = header_offset + ENTRY_HEADER_LEN;
+        let (data_offset, end) = match name_offset
+            .checked_add(filename_size)
+            .and_then(|data_offset| Some((data_offset, data_offset.checked_add(data_size)?)))
+        {
+            Some((data_offset, end)) if end <= buf.len() => (data_offset, end),
+            _ => return Err(ArchivePackageError::UnexpectedEntryEof),
+        };
 
-        let data_size = u32::from_le_bytes([
-            buf[*offset],
-            buf[*offset + 1],
-            buf[*offset + 2],
-            buf[*offset + 3],
-        ]) as usize;
-        *offset += 4;
+        let name = std::str::from_utf8(&buf[name_offset..data_offset])
+            .map_err(|_| ArchivePackageError::InvalidArchiveEntryName)?;
+        let data = &buf[data_offset..end];
+        *offset = end;
 
-        if buf.len() < *offset + filename_size + data_size {
-            return Err(ArchivePackageError::UnexpectedEntryEof);
-        }
+        Ok(Some(Self {
+            name,
+            data,
+            header_offset,
+            data_offset,
+            frame_len: end - header_offset,
+        }))
+    }
+}
 
-        let name = std::str::from_utf8(&buf[*offset..*offset + filename_size])
-            .map_err(|_| ArchivePackageError::InvalidArchiveEntryName)?;
-        *offset += filename_size;
+/// Classifies bytes after the last complete entry
+pub(crate) fn check_package_tail(tail: &[u8], offset: u64) -> Result<(), ArchivePackageError> {
+    let remaining = tail.len();
+    if remaining == 0 {
+        return Ok(());
+    }
+
+    let prefix_len = std::cmp::min(remaining, ARCHIVE_ENTRY_PREFIX.len());
+    if tail[..prefix_len] == ARCHIVE_ENTRY_PREFIX[..prefix_len] {
+        Err(ArchivePackageError::TruncatedEntryHeader { offset, remaining })
+    } else {
+        Err(ArchivePackageError::TrailingData { offset, remaining })
+    }
+}
 
-        let data = &buf[*offset..*offset + data_size];
-        *offset += data_size;
+/// Computes data offset and full frame length of the entry, checking for overflow
+pub(crate) fn entry_layout(
+    header_offset: usize,
+    filename_size: usize,
+    data_size: usize,
+) -> Result<(usize, usize), ArchivePackageError> {
+    let frame_len = ENTRY_HEADER_LEN
+        .checked_add(filename_size)
+        .and_then(|len| len.checked_add(data_size))
+        .ok_or(ArchivePackageError::OffsetOverflow)?;
 
-        Ok(Some(Self { name, data }))
+    // End of the frame must also be representable
+    header_offset
+        .checked_add(frame_len)
+        .ok_or(ArchivePackageError::OffsetOverflow)?;
+
+    Ok((header_offset + ENTRY_HEADER_LEN + filename_size, frame_len))
+}
+
+/// Parses entry prefix, filename size and data size
+pub(crate) fn parse_entry_header(header: &[u8]) -> Result<(usize, usize), ArchivePackageError> {
+    if header[..2] != ARCHIVE_ENTRY_PREFIX {
+        return Err(ArchivePackageError::InvalidArchiveEntryHeader);
     }
+
+    let filename_size = u16::from_le_bytes([header[2], header[3]]) as usize;
+    let data_size = u32::from_le_bytes([header[4], header[5], header[6], header[7]]) as usize;
+
+    Ok((filename_size, data_size))
 }
 
 #[derive(thiserror::Error, Debug)]
@@ -91,9 +528,224 @@ pub enum ArchivePackageError {
     InvalidArchiveEntryName,
     #[error("Unexpected entry eof")]
     UnexpectedEntryEof,
+    #[error("Truncated entry header at offset {offset} ({remaining} bytes left)")]
+    TruncatedEntryHeader { offset: u64, remaining: usize },
+    #[error("Trailing data at offset {offset} ({remaining} bytes left)")]
+    TrailingData { offset: u64, remaining: usize },
     #[error("Too small initial batch")]
     TooSmallInitialBatch,
+    #[error("Too long archive entry name")]
+    TooLongEntryName,
+    #[error("Too large archive entry data")]
+    TooLargeEntryData,
+    #[error("Offset {offset} is out of package bounds")]
+    OffsetOutOfBounds { offset: usize },
+    #[error("Package offset overflow")]
+    OffsetOverflow,
+    #[error("IO error")]
+    Io(#[from] std::io::Error),
 }
 
-const ARCHIVE_PREFIX: [u8; 4] = u32::to_le_bytes(0xae8fdd01);
+pub(crate) const ARCHIVE_PREFIX: [u8; 4] = u32::to_le_bytes(0xae8fdd01);
 const ARCHIVE_ENTRY_PREFIX: [u8; 2] = u16::to_le_bytes(0x1e8b);
+pub(crate) const ENTRY_HEADER_LEN: usize = 8;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry_name(seqno: u32) -> String {
+        format!(
+            "block_(-1,8000000000000000,{seqno}):{}:{}",
+            "AB".repeat(32),
+            "CD".repeat(32)
+        )
+    }
+
+    fn test_entries() -> Vec<(String, Vec<u8>)> {
+        (1..=3)
+            .map(|seqno| (entry_name(seqno), vec![seqno as u8; seqno as usize * 10]))
+            .collect()
+    }
+
+    fn write_package(entries: &[(String, Vec<u8>)]) -> Vec<u8> {
+        let mut writer = ArchivePackageWriter::new(Vec::new()).unwrap();
+        for (name, data) in entries {
+            writer.write_entry(name, data).unwrap();
+        }
+        writer.finish().unwrap()
+    }
+
+    #[test]
+    fn writer_output_is_readable() {
+        let entries = test_entries();
+        let package = write_package(&entries);
+
+        let expected_len = ARCHIVE_PREFIX.len()
+            + entries
+                .iter()
+                .map(|(name, data)| ENTRY_HEADER_LEN + name.len() + data.len())
+                .sum::<usize>();
+        assert_eq!(package.len(), expected_len);
+
+        let mut reader = ArchivePackageViewReader::new(&package)
+            .unwrap()
+            .with_strict_eof(true);
+        let mut rewriter = ArchivePackageWriter::new(Vec::new()).unwrap();
+        for (name, data) in &entries {
+            let entry = reader.read_next().unwrap().unwrap();
+            assert_eq!(entry.name, name);
+            assert_eq!(entry.data, data.as_slice());
+            rewriter.write_entry_view(&entry).unwrap();
+        }
+        assert!(reader.read_next().unwrap().is_none());
+
+        assert_eq!(rewriter.finish().unwrap(), package);
+    }
+
+    #[test]
+    fn salvage_reader_skips_garbage() {
+        let entries = test_entries();
+        let package = write_package(&entries);
+
+        // Garbage with a fake entry prefix to check resync after an invalid header
+        let garbage = [
+            0xff, 0x8b, 0x1e, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x00, 0x01,
+        ];
+        let first_end =
+            ARCHIVE_PREFIX.len() + ENTRY_HEADER_LEN + entries[0].0.len() + entries[0].1.len();
+
+        let mut corrupted = package[..first_end].to_vec();
+        corrupted.extend_from_slice(&garbage);
+        corrupted.extend_from_slice(&package[first_end..]);
+
+        let mut reader = ArchivePackageSalvageReader::new(&corrupted);
+        let mut recovered = Vec::new();
+        while let Some(entry) = reader.read_next() {
+            recovered.push((entry.name.to_owned(), entry.data.to_vec()));
+        }
+
+        assert_eq!(recovered, entries);
+        assert_eq!(
+            reader.corrupted_ranges(),
+            &[first_end..first_end + garbage.len()]
+        );
+    }
+
+    #[test]
+    fn strict_reader_classifies_tails() {
+        let entries = test_entries();
+        let package = write_package(&entries);
+        let offset = package.len() as u64;
+
+        // Returns results of all readers
+        let read_all = |tail: &[u8], strict: bool| {
+            let mut data = package.clone();
+            data.extend_from_slice(tail);
+
+            let view_result = ArchivePackageViewReader::new(&data)
+                .unwrap()
+                .with_strict_eof(strict)
+                .collect::<Result<Vec<_>, _>>()
+                .map(|entries| entries.len());
+
+            let mut reader = ArchivePackageStreamReader::new(data.as_slice())
+                .unwrap()
+                .with_strict_eof(strict);
+            let stream_result = std::iter::from_fn(|| reader.read_next().transpose())
+                .collect::<Result<Vec<_>, _>>()
+                .map(|entries| entries.len());
+
+            let results = vec![view_result, stream_result];
+
+            #[cfg(feature = "tokio")]
+            let results = {
+                use futures_util::FutureExt;
+
+                let read = async {
+                    let mut reader = crate::ArchivePackageAsyncReader::new(data.as_slice())
+                        .await
+                        .unwrap()
+                        .with_strict_eof(strict);
+                    let mut count = 0;
+                    while reader.read_next().await?.is_some() {
+                        count += 1;
+                    }
+                    Ok::<_, ArchivePackageError>(count)
+                };
+
+                let mut results = results;
+                results.push(read.now_or_never().expect("Slice reader must not wait"));
+                results
+            };
+
+            results
+        };
+
+        // Empty tail
+        for strict in [false, true] {
+            for result in read_all(&[], strict) {
+                assert_eq!(result.unwrap(), entries.len());
+            }
+        }
+
+        // Short tails are ignored in non-strict mode
+        for len in 1..ENTRY_HEADER_LEN {
+            let tail = vec![0xff; len];
+            for result in read_all(&tail, false) {
+                assert_eq!(result.unwrap(), entries.len());
+            }
+        }
+
+        // Short tails which look like a truncated entry header
+        for len in 1..ENTRY_HEADER_LEN {
+            let mut tail = ARCHIVE_ENTRY_PREFIX.to_vec();
+            tail.resize(ENTRY_HEADER_LEN, 0);
+            tail.truncate(len);
+
+            for result in read_all(&tail, true) {
+                assert!(matches!(
+                    result,
+                    Err(ArchivePackageError::TruncatedEntryHeader { offset: o, remaining })
+                        if o == offset && remaining == len
+                ));
+            }
+        }
+
+        // Short tails of garbage
+        for len in 1..ENTRY_HEADER_LEN {
+            let tail = vec![0xff; len];
+            for result in read_all(&tail, true) {
+                assert!(matches!(
+                    result,
+                    Err(ArchivePackageError::TrailingData { offset: o, remaining })
+                        if o == offset && remaining == len
+                ));
+            }
+        }
+
+        // Full header of garbage is an invalid entry in any mode
+        for strict in [false, true] {
+            for result in read_all(&[0xff; ENTRY_HEADER_LEN + 1], strict) {
+                assert!(matches!(
+                    result,
+                    Err(ArchivePackageError::InvalidArchiveEntryHeader)
+                ));
+            }
+        }
+
+        // Full header of the entry without data
+        for strict in [false, true] {
+            let mut tail = ARCHIVE_ENTRY_PREFIX.to_vec();
+            tail.extend_from_slice(&1u16.to_le_bytes());
+            tail.extend_from_slice(&1u32.to_le_bytes());
+
+            for result in read_all(&tail, strict) {
+                assert!(matches!(
+                    result,
+                    Err(ArchivePackageError::UnexpectedEntryEof)
+                ));
+            }
+        }
+    }
+}
@@ -0,0 +1,98 @@
+use futures_util::stream::Stream;
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+use crate::archive_package::*;
+
+pub struct ArchivePackageAsyncReader<R> {
+    reader: R,
+    state: StreamReaderState,
+}
+
+impl<R: AsyncRead + Unpin> ArchivePackageAsyncReader<R> {
+    pub async fn new(mut reader: R) -> Result<Self, ArchivePackageError> {
+        let mut header = [0; 4];
+        read_exact_or(
+            &mut reader,
+            &mut header,
+            ArchivePackageError::UnexpectedArchiveEof,
+        )
+        .await?;
+        Ok(Self {
+            reader,
+            state: StreamReaderState::new(&header)?,
+        })
+    }
+
+    /// Fails on truncated entry headers and trailing bytes instead of
+    /// treating them as the end of the package
+    pub fn with_strict_eof(mut self, strict: bool) -> Self {
+        self.state.strict = strict;
+        self
+    }
+
+    pub async fn read_next(&mut self) -> Result<Option<ArchivePackageEntry>, ArchivePackageError> {
+        let mut header = [0; ENTRY_HEADER_LEN];
+        let filled = read_full(&mut self.reader, &mut header).await?;
+        let (filename_size, data_size) = match self.state.parse_header(&header[..filled])? {
+            Some(sizes) => sizes,
+            None => return Ok(None),
+        };
+
+        let mut name = vec![0; filename_size];
+        read_exact_or(
+            &mut self.reader,
+            &mut name,
+            ArchivePackageError::UnexpectedEntryEof,
+        )
+        .await?;
+        let name = parse_entry_name(name)?;
+
+        // NOTE: data is read incrementally to not allocate the whole buffer
+        // before the reader actually has it
+        let mut data = Vec::new();
+        (&mut self.reader)
+            .take(data_size as u64)
+            .read_to_end(&mut data)
+            .await?;
+
+        self.state.make_entry(name, data, data_size).map(Some)
+    }
+
+    /// Converts reader into a stream of owned entries
+    pub fn into_stream(
+        self,
+    ) -> impl Stream<Item = Result<ArchivePackageEntry, ArchivePackageError>> {
+        futures_util::stream::try_unfold(self, |mut reader| async move {
+            Ok(reader.read_next().await?.map(|entry| (entry, reader)))
+        })
+    }
+
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+}
+
+/// Async version of [`read_full`](crate::archive_package::read_full)
+async fn read_full<R: AsyncRead + Unpin>(reader: &mut R, buf: &mut [u8]) -> std::io::Result<usize> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match reader.read(&mut buf[filled..]).await {
+            Ok(0) => break,
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(filled)
+}
+
+async fn read_exact_or<R: AsyncRead + Unpin>(
+    reader: &mut R,
+    buf: &mut [u8],
+    eof_error: ArchivePackageError,
+) -> Result<(), ArchivePackageError> {
+    match reader.read_exact(buf).await {
+        Ok(_) => Ok(()),
+        Err(e) => Err(map_eof_error(e, eof_error)),
+    }
+}
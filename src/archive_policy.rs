@@ -0,0 +1,27 @@
+use crate::archive_data::ArchiveData;
+
+/// Rules for the archive layout. Nothing is checked by default
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub struct ArchivePolicy {
+    /// Max number of masterchain blocks in one archive
+    pub max_mc_block_count: Option<usize>,
+    /// Archive slice size in masterchain blocks. Slices are counted from the latest
+    /// key block, so archives must start at the slice boundary and must not cross it
+    pub slice_size: Option<u32>,
+    /// Key blocks can only be the first masterchain block in the archive
+    pub key_block_first: bool,
+}
+
+impl ArchivePolicy {
+    /// Default archive slice size
+    pub const SLICE_SIZE: u32 = 100;
+
+    /// Layout of the archives produced by the node
+    pub fn node() -> Self {
+        Self {
+            max_mc_block_count: Some(ArchiveData::MAX_MC_BLOCK_COUNT),
+            slice_size: Some(Self::SLICE_SIZE),
+            key_block_first: true,
+        }
+    }
+}
@@ -0,0 +1,181 @@
+use std::collections::BTreeMap;
+
+use ton_block::Deserializable;
+
+use crate::archive_data::ArchiveDataError;
+
+/// Validator set from the key block config which signs the following masterchain blocks
+pub struct KeyBlockValidators {
+    seqno: u32,
+    validator_set: ton_block::ValidatorSet,
+    catchain_config: ton_block::CatchainConfig,
+}
+
+impl KeyBlockValidators {
+    /// Reads the validator set from the key block
+    pub fn from_key_block(block: &ton_block::Block) -> Result<Self, ArchiveDataError> {
+        let info = block
+            .read_info()
+            .map_err(|_| ArchiveDataError::InvalidBlockData)?;
+        if !info.key_block() {
+            return Err(ArchiveDataError::NotAKeyBlock);
+        }
+
+        let extra = block
+            .read_extra()
+            .map_err(|_| ArchiveDataError::InvalidBlockData)?;
+        let custom = extra
+            .read_custom()
+            .map_err(|_| ArchiveDataError::InvalidBlockData)?
+            .ok_or(ArchiveDataError::InvalidKeyBlockConfig)?;
+        let config = custom
+            .config()
+            .ok_or(ArchiveDataError::InvalidKeyBlockConfig)?;
+
+        let validator_set = config
+            .validator_set()
+            .map_err(|_| ArchiveDataError::InvalidKeyBlockConfig)?;
+        let catchain_config = config
+            .catchain_config()
+            .map_err(|_| ArchiveDataError::InvalidKeyBlockConfig)?;
+
+        Ok(Self {
+            seqno: info.seq_no(),
+            validator_set,
+            catchain_config,
+        })
+    }
+
+    /// Reads the validator set from the serialized key block (e.g. trusted key block file)
+    pub fn from_boc(mut data: &[u8]) -> Result<Self, ArchiveDataError> {
+        let root = ton_types::deserialize_tree_of_cells(&mut data)
+            .map_err(|_| ArchiveDataError::InvalidBlockData)?;
+        let block = ton_block::Block::construct_from(&mut root.into())
+            .map_err(|_| ArchiveDataError::InvalidBlockData)?;
+        Self::from_key_block(&block)
+    }
+
+    /// Key block seqno
+    pub fn seqno(&self) -> u32 {
+        self.seqno
+    }
+
+    /// Checks that the block is signed by validators with more than 2/3 of the total weight
+    pub fn check_signatures(
+        &self,
+        info: &ton_block::BlockInfo,
+        proof: &ton_block::BlockProof,
+    ) -> Result<(), ArchiveDataError> {
+        let block_id = &proof.proof_for;
+        let signatures = proof
+            .signatures
+            .as_ref()
+            .ok_or(ArchiveDataError::BlockSignaturesNotFound)?;
+
+        let (validators, list_hash_short) = self
+            .validator_set
+            .calc_subset(
+                &self.catchain_config,
+                block_id.shard_id.shard_prefix_with_tag(),
+                block_id.shard_id.workchain_id(),
+                info.gen_catchain_seqno(),
+                info.gen_utime(),
+            )
+            .map_err(|_| ArchiveDataError::InvalidKeyBlockConfig)?;
+
+        let validator_info = &signatures.validator_info;
+        if validator_info.catchain_seqno != info.gen_catchain_seqno() {
+            return Err(ArchiveDataError::CatchainSeqnoMismatch {
+                expected: info.gen_catchain_seqno(),
+                found: validator_info.catchain_seqno,
+            });
+        }
+        if validator_info.validator_list_hash_short != list_hash_short {
+            return Err(ArchiveDataError::ValidatorListHashMismatch {
+                expected: list_hash_short,
+                found: validator_info.validator_list_hash_short,
+            });
+        }
+
+        let total_weight = validators.iter().map(|item| item.weight).sum::<u64>();
+
+        let data = ton_block::Block::build_data_for_sign(&block_id.root_hash, &block_id.file_hash);
+        let weight = signatures
+            .pure_signatures
+            .check_signatures(validators, &data)
+            .map_err(|_| ArchiveDataError::InvalidSignatures)?;
+
+        if weight as u128 * 3 <= total_weight as u128 * 2 {
+            return Err(ArchiveDataError::NotEnoughSignatures {
+                weight,
+                total_weight,
+            });
+        }
+
+        Ok(())
+    }
+}
+
+/// Trusted key blocks and key blocks verified by them
+#[derive(Default)]
+pub struct KeyBlockSet {
+    key_blocks: BTreeMap<u32, KeyBlockValidators>,
+}
+
+impl KeyBlockSet {
+    /// Adds the trusted key block. Known key blocks are not replaced
+    pub fn insert(&mut self, validators: KeyBlockValidators) {
+        self.key_blocks
+            .entry(validators.seqno())
+            .or_insert(validators);
+    }
+
+    /// Adds the key block after checking its signatures with the already known key blocks.
+    /// Key blocks must be added in seqno order
+    pub fn add_verified(&mut self, key_block: &KeyBlockEntry) -> Result<(), ArchiveDataError> {
+        let info = key_block
+            .block
+            .read_info()
+            .map_err(|_| ArchiveDataError::InvalidBlockData)?;
+        self.check_signatures(&info, &key_block.proof)?;
+
+        if !self.contains(info.seq_no()) {
+            self.insert(KeyBlockValidators::from_key_block(&key_block.block)?);
+        }
+        Ok(())
+    }
+
+    pub fn get(&self, seqno: u32) -> Option<&KeyBlockValidators> {
+        self.key_blocks.get(&seqno)
+    }
+
+    pub fn contains(&self, seqno: u32) -> bool {
+        self.key_blocks.contains_key(&seqno)
+    }
+
+    /// Checks the block signatures with the validators of its previous key block
+    pub fn check_signatures(
+        &self,
+        info: &ton_block::BlockInfo,
+        proof: &ton_block::BlockProof,
+    ) -> Result<(), ArchiveDataError> {
+        let seqno = info.prev_key_block_seqno();
+        match self.get(seqno) {
+            Some(validators) => validators.check_signatures(info, proof),
+            None => Err(ArchiveDataError::KeyBlockNotFound { seqno }),
+        }
+    }
+}
+
+/// Key block with its proof which are not verified yet
+#[derive(Clone)]
+pub struct KeyBlockEntry {
+    pub block: ton_block::Block,
+    pub proof: ton_block::BlockProof,
+}
+
+impl KeyBlockEntry {
+    pub fn seqno(&self) -> u32 {
+        self.proof.proof_for.seq_no
+    }
+}
@@ -1,8 +1,20 @@
 pub use archive_data::*;
+pub use archive_index::*;
 pub use archive_package::*;
+#[cfg(feature = "tokio")]
+pub use archive_package_async::*;
+pub use archive_policy::*;
+pub use key_block_validators::*;
 pub use package_entry_id::*;
+pub use validation_report::*;
 
 mod archive_data;
+mod archive_index;
 mod archive_package;
+#[cfg(feature = "tokio")]
+mod archive_package_async;
+mod archive_policy;
+mod key_block_validators;
 mod package_entry_id;
 pub mod utils;
+mod validation_report;
@@ -1,11 +1,12 @@
 use std::collections::{BTreeSet, HashMap};
 use std::fs::File;
-use std::io::Read;
-use std::path::PathBuf;
+use std::io::{Read, Seek};
+use std::path::{Path, PathBuf};
 
 use anyhow::{Context, Result};
 use ever_archive::utils::*;
 use ever_archive::*;
+use rayon::prelude::*;
 
 fn main() {
     if let Err(e) = argh::from_env::<App>().run() {
@@ -26,17 +27,20 @@ impl App {
         match self.subcommand {
             Subcommand::Check(cmd) => cmd.run(),
             Subcommand::List(cmd) => cmd.run(),
+            Subcommand::Index(cmd) => cmd.run(),
+            Subcommand::Repair(cmd) => cmd.run(),
         }
     }
 }
 
-struct ListEntry<'a> {
+struct ListEntry {
     package_id: Result<PackageEntryId<ton_block::BlockIdExt>, PackageEntryIdError>,
-    data: &'a [u8],
+    size: usize,
     with_size: bool,
+    offsets: Option<EntryOffsets>,
 }
 
-impl std::fmt::Display for ListEntry<'_> {
+impl std::fmt::Display for ListEntry {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         match &self.package_id {
             Ok(package_id) => f.write_str(&package_id.filename()),
@@ -44,18 +48,27 @@ impl std::fmt::Display for ListEntry<'_> {
         }?;
 
         if self.with_size {
-            f.write_fmt(format_args!(" {}", &self.data.len().to_string()))?;
+            f.write_fmt(format_args!(" {}", &self.size.to_string()))?;
+        }
+
+        if let Some((header_offset, data_offset, frame_len)) = self.offsets {
+            f.write_fmt(format_args!(" {header_offset} {data_offset} {frame_len}"))?;
         }
 
         Ok(())
     }
 }
 
+/// Entry header offset, data offset and frame length
+type EntryOffsets = (u64, u64, usize);
+
 #[derive(argh::FromArgs)]
 #[argh(subcommand)]
 enum Subcommand {
     Check(CmdCheck),
     List(CmdList),
+    Index(CmdIndex),
+    Repair(CmdRepair),
 }
 
 /// Verifies the archive
@@ -69,75 +82,307 @@ struct CmdCheck {
     /// shows all key blocks, merges and splits if specified
     #[argh(switch, short = 'a')]
     show_features: bool,
+
+    /// number of threads used for checking archives and deserializing blocks.
+    /// all cores are used by default
+    #[argh(option, short = 'j')]
+    jobs: Option<usize>,
+
+    /// output format: text, json or ndjson. text is used by default
+    #[argh(option, short = 'f', default = "OutputFormat::Text")]
+    format: OutputFormat,
+
+    /// check the layout of the archives produced by the node. other layout options
+    /// override its values
+    #[argh(switch)]
+    node_policy: bool,
+
+    /// max number of masterchain blocks in one archive. not checked by default
+    #[argh(option)]
+    max_mc_blocks: Option<usize>,
+
+    /// archive slice size in masterchain blocks counted from the latest key block.
+    /// not checked by default (the node uses 100)
+    #[argh(option)]
+    slice_size: Option<u32>,
+
+    /// require key blocks to be the first masterchain block in the archive
+    #[argh(switch)]
+    key_block_first: bool,
+
+    /// verify validator signatures of masterchain blocks
+    #[argh(switch)]
+    verify_signatures: bool,
+
+    /// path to the trusted key block used to verify signatures. can be specified multiple times
+    #[argh(option)]
+    key_block: Vec<PathBuf>,
 }
 
 impl CmdCheck {
     fn run(self) -> Result<()> {
-        match self.path {
-            Some(path) if path.is_dir() => {
-                let mut files = Vec::new();
-
-                let mut entries = std::fs::read_dir(path)?;
-                while let Some(entry) = entries.next() {
-                    let path = entry?.path();
-                    if path.is_file() {
-                        files.push(path);
-                    }
-                }
+        if let Some(jobs) = self.jobs {
+            rayon::ThreadPoolBuilder::new()
+                .num_threads(jobs)
+                .build_global()
+                .context("Failed to create thread pool")?;
+        }
 
-                files.sort();
+        let mut key_blocks = KeyBlockSet::default();
+        for path in &self.key_block {
+            let data = std::fs::read(path)?;
+            let validators = KeyBlockValidators::from_boc(&data)
+                .with_context(|| format!("Invalid key block {}", path.display()))?;
+            key_blocks.insert(validators);
+        }
 
-                let pg = indicatif::ProgressBar::new(files.len() as u64);
-                for path in files {
-                    Self::check_archive(Some(path), self.show_features)?;
-                    pg.inc(1);
+        match &self.path {
+            Some(path) if path.is_dir() => self.check_dir(path, key_blocks),
+            path => {
+                let result = self.check_archive(path.clone(), &key_blocks);
+                let outcome = ArchiveOutcome::new(path.clone(), result);
+                match self.format {
+                    OutputFormat::Text => {
+                        let checked = outcome.into_result()?;
+                        print!("{}", checked.display(self.show_features));
+                        Ok(())
+                    }
+                    OutputFormat::Json | OutputFormat::Ndjson => {
+                        let valid = outcome.is_valid();
+                        self.format.print_one(&ArchiveRecord::from(&outcome))?;
+                        if !valid {
+                            anyhow::bail!("Archive is invalid");
+                        }
+                        Ok(())
+                    }
                 }
-                Ok(())
             }
-            path => Self::check_archive(path, self.show_features),
         }
     }
 
-    fn check_archive(path: Option<PathBuf>, show_features: bool) -> Result<()> {
-        use std::collections::hash_map;
+    fn check_dir(&self, path: &Path, mut key_blocks: KeyBlockSet) -> Result<()> {
+        let mut files = Vec::new();
 
-        let archive = RawArchive::new(path)?;
-        let archive = archive.view()?;
+        let mut entries = std::fs::read_dir(path)?;
+        while let Some(entry) = entries.next() {
+            let path = entry?.path();
+            // NOTE: index sidecars are stored next to the archives by default
+            if path.is_file() && !is_index_file(&path)? {
+                files.push(path);
+            }
+        }
+
+        files.sort();
 
-        let archive = ArchiveData::new(archive.as_ref()).context("Failed to parse archive")?;
+        if self.verify_signatures {
+            // Collect key blocks from all archives first, so that each archive
+            // can be checked with the latest key block from the previous ones
+            let mut archive_key_blocks = files
+                .par_iter()
+                .flat_map_iter(|path| {
+                    read_key_blocks(path).unwrap_or_else(|e| {
+                        // NOTE: signatures of the next archives can't be verified
+                        // without key blocks from this one
+                        eprintln!("Failed to read key blocks from {}: {e:#}", path.display());
+                        Vec::new()
+                    })
+                })
+                .collect::<Vec<_>>();
+            archive_key_blocks.sort_by_key(KeyBlockEntry::seqno);
 
-        struct SimpleList {
-            name: &'static str,
-            ids: BTreeSet<ton_block::BlockIdExt>,
+            for key_block in &archive_key_blocks {
+                // NOTE: invalid key blocks are reported when their archives are checked
+                key_blocks.add_verified(key_block).ok();
+            }
         }
+        let key_blocks = &key_blocks;
 
-        impl SimpleList {
-            fn new(name: &'static str) -> Self {
-                Self {
-                    name,
-                    ids: Default::default(),
-                }
+        let pg = indicatif::ProgressBar::new(files.len() as u64);
+        let mut outcomes = files
+            .into_par_iter()
+            .map(|path| {
+                let result = self.check_archive(Some(path.clone()), key_blocks);
+                pg.inc(1);
+                ArchiveOutcome::new(Some(path), result)
+            })
+            .collect::<Vec<_>>();
+        pg.finish_and_clear();
+
+        // NOTE: file names are not guaranteed to be sorted by seqno
+        let mut boundaries = outcomes
+            .iter()
+            .enumerate()
+            .filter_map(|(i, outcome)| {
+                let boundary = outcome.checked.as_ref()?.boundary.as_ref()?;
+                Some((i, boundary.first_mc_block.seq_no))
+            })
+            .collect::<Vec<_>>();
+        boundaries.sort_by_key(|(_, seqno)| *seqno);
+
+        let mut continuity_errors = Vec::new();
+        let mut gaps = Vec::new();
+        for pair in boundaries.windows(2) {
+            let (prev_index, next_index) = (pair[0].0, pair[1].0);
+            let prev = &outcomes[prev_index];
+            let next = &outcomes[next_index];
+
+            let prev_boundary = prev.boundary().expect("Must be present");
+            let next_boundary = next.boundary().expect("Must be present");
+
+            // NOTE: missing blocks can be in the invalid archives which have no boundary,
+            // so the gap is reported separately instead of failing the next archive
+            let last_seqno = prev_boundary.last_mc_block.seq_no;
+            let next_seqno = next_boundary.first_mc_block.seq_no;
+            if next_seqno > last_seqno.saturating_add(1) {
+                gaps.push((
+                    next_index,
+                    ArchiveGap {
+                        previous: prev.path.clone(),
+                        first_seqno: last_seqno + 1,
+                        last_seqno: next_seqno - 1,
+                    },
+                ));
+                continue;
             }
 
-            fn is_empty(&self) -> bool {
-                self.ids.is_empty()
+            if let Err(e) = prev_boundary.check_next(next_boundary) {
+                let prev_path = prev.path.as_deref().unwrap_or_else(|| Path::new("-"));
+                let e = anyhow::Error::new(e)
+                    .context(format!("Doesn't continue {}", prev_path.display()));
+                continuity_errors.push((next_index, e));
             }
         }
+        for (index, e) in continuity_errors {
+            outcomes[index].errors.push(e);
+        }
+        let gap_count = gaps.len();
+        for (index, gap) in gaps {
+            outcomes[index].gap_before = Some(gap);
+        }
+
+        let total = outcomes.len();
+        let failed = outcomes
+            .iter()
+            .filter(|outcome| !outcome.is_valid())
+            .count();
 
-        impl std::fmt::Display for SimpleList {
-            fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-                f.write_fmt(format_args!("{}:\n", self.name))?;
-                for id in &self.ids {
-                    f.write_fmt(format_args!("\t{id}\n"))?;
+        match self.format {
+            OutputFormat::Text => {
+                for outcome in &outcomes {
+                    if let Some(checked) = &outcome.checked {
+                        if !checked.report.has_errors() {
+                            print!("{}", checked.display(self.show_features));
+                        }
+                    }
+                }
+
+                if failed > 0 {
+                    eprintln!("Failed archives:");
+                    for outcome in outcomes.iter().filter(|outcome| !outcome.is_valid()) {
+                        let path = outcome.path.as_deref().unwrap_or_else(|| Path::new("-"));
+                        for e in &outcome.errors {
+                            eprintln!("\t{}: {e:#}", path.display());
+                        }
+                        if let Some(checked) = &outcome.checked {
+                            for problem in checked.report.errors() {
+                                eprintln!("\t{}: {problem}", path.display());
+                            }
+                        }
+                    }
+                }
+
+                if gap_count > 0 {
+                    eprintln!("Missing masterchain blocks:");
+                    for outcome in &outcomes {
+                        if let Some(gap) = &outcome.gap_before {
+                            let path = outcome.path.as_deref().unwrap_or_else(|| Path::new("-"));
+                            let previous =
+                                gap.previous.as_deref().unwrap_or_else(|| Path::new("-"));
+                            eprintln!(
+                                "\t{}..={} between {} and {}",
+                                gap.first_seqno,
+                                gap.last_seqno,
+                                previous.display(),
+                                path.display()
+                            );
+                        }
+                    }
                 }
+            }
+            OutputFormat::Json | OutputFormat::Ndjson => {
+                let records = outcomes.iter().map(ArchiveRecord::from).collect::<Vec<_>>();
+                self.format.print_all(&records)?;
+            }
+        }
+
+        if failed > 0 || gap_count > 0 {
+            anyhow::bail!(
+                "{failed} of {total} archives are invalid, {gap_count} gaps between archives"
+            );
+        }
+        Ok(())
+    }
+
+    fn policy(&self) -> ArchivePolicy {
+        let mut policy = if self.node_policy {
+            ArchivePolicy::node()
+        } else {
+            ArchivePolicy::default()
+        };
+        if let Some(max_mc_blocks) = self.max_mc_blocks {
+            policy.max_mc_block_count = Some(max_mc_blocks);
+        }
+        if let Some(slice_size) = self.slice_size {
+            policy.slice_size = Some(slice_size);
+        }
+        policy.key_block_first |= self.key_block_first;
+        policy
+    }
+
+    fn check_archive(
+        &self,
+        path: Option<PathBuf>,
+        key_blocks: &KeyBlockSet,
+    ) -> Result<CheckedArchive> {
+        use std::collections::hash_map;
 
-                Ok(())
+        let raw_archive;
+        let raw_archive_view;
+        let entries;
+        // NOTE: blocks are deserialized lazily, so that all invalid entries
+        // are reported instead of the first one
+        let archive = match path {
+            Some(path) => {
+                raw_archive = RawArchive::open(&path)?;
+                raw_archive_view = raw_archive.view()?;
+                ArchiveData::new_lazy(raw_archive_view.as_ref())
+            }
+            None => {
+                entries = read_stdin_entries()?;
+                ArchiveData::from_entries_lazy(&entries)
             }
         }
+        .context("Failed to parse archive")?;
+
+        let policy = self.policy();
 
-        let mut key_blocks = SimpleList::new("Key blocks");
-        let mut merges = SimpleList::new("Merges");
-        let mut splits = SimpleList::new("Splits");
+        let mut checked = CheckedArchive {
+            boundary: None,
+            report: archive.validate_with_policy(&policy),
+            key_blocks: SimpleList::new("Key blocks"),
+            merges: SimpleList::new("Merges"),
+            splits: SimpleList::new("Splits"),
+            first_blocks: SimpleList::new("First blocks"),
+            last_blocks: SimpleList::new("Last blocks"),
+        };
+        if self.verify_signatures {
+            checked
+                .report
+                .extend(archive.validate_signatures(key_blocks));
+        }
+        if checked.report.has_errors() {
+            return Ok(checked);
+        }
 
         fn insert_id_if(
             map: &mut SeqNoMap,
@@ -191,6 +436,12 @@ impl CmdCheck {
             None => Default::default(),
         };
 
+        checked.boundary = Some(
+            archive
+                .boundary()
+                .context("Failed to read archive boundary")?,
+        );
+
         for (id, entry) in archive.blocks {
             let ((block, _), _) = entry
                 .get_data()
@@ -200,43 +451,152 @@ impl CmdCheck {
                 .read_info()
                 .with_context(|| format!("Invalid block data ({id})"))?;
 
-            info.read_master_id()?;
-
             if info.key_block() {
-                key_blocks.ids.insert(id.clone());
+                checked.key_blocks.ids.insert(id.clone());
             }
             if info.after_merge() {
-                merges.ids.insert(id.clone());
+                checked.merges.ids.insert(id.clone());
             }
             if info.after_split() {
-                splits.ids.insert(id.clone());
+                checked.splits.ids.insert(id.clone());
             }
 
             insert_id_if(&mut first_blocks, &id, |v| id.seq_no < v.seq_no);
             insert_id_if(&mut last_blocks, &id, |v| id.seq_no > v.seq_no);
         }
 
-        let first_blocks = SimpleList {
-            name: "First blocks",
-            ids: first_blocks.into_values().collect(),
-        };
-        let last_blocks = SimpleList {
-            name: "Last blocks",
-            ids: last_blocks.into_values().collect(),
-        };
+        checked.first_blocks.ids = first_blocks.into_values().collect();
+        checked.last_blocks.ids = last_blocks.into_values().collect();
+
+        Ok(checked)
+    }
+}
+
+struct CheckedArchive {
+    /// Present only for archives without errors
+    boundary: Option<ArchiveBoundary>,
+    report: ValidationReport,
+    key_blocks: SimpleList,
+    merges: SimpleList,
+    splits: SimpleList,
+    first_blocks: SimpleList,
+    last_blocks: SimpleList,
+}
+
+impl CheckedArchive {
+    fn display(&self, show_features: bool) -> String {
+        use std::fmt::Write;
+
+        let mut output = String::new();
+        for warning in self.report.warnings() {
+            let _ = writeln!(output, "{warning}");
+        }
 
         if show_features {
-            for list in [key_blocks, merges, splits, first_blocks, last_blocks] {
+            for list in [
+                &self.key_blocks,
+                &self.merges,
+                &self.splits,
+                &self.first_blocks,
+                &self.last_blocks,
+            ] {
                 if !list.is_empty() {
-                    print!("{list}");
+                    let _ = write!(output, "{list}");
                 }
             }
         }
 
+        output
+    }
+}
+
+struct SimpleList {
+    name: &'static str,
+    ids: BTreeSet<ton_block::BlockIdExt>,
+}
+
+impl SimpleList {
+    fn new(name: &'static str) -> Self {
+        Self {
+            name,
+            ids: Default::default(),
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.ids.is_empty()
+    }
+}
+
+impl std::fmt::Display for SimpleList {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_fmt(format_args!("{}:\n", self.name))?;
+        for id in &self.ids {
+            f.write_fmt(format_args!("\t{id}\n"))?;
+        }
+
         Ok(())
     }
 }
 
+/// Result of the archive check with all related errors
+struct ArchiveOutcome {
+    path: Option<PathBuf>,
+    checked: Option<CheckedArchive>,
+    errors: Vec<anyhow::Error>,
+    /// Masterchain blocks which are missing between the previous archive and this one
+    gap_before: Option<ArchiveGap>,
+}
+
+struct ArchiveGap {
+    previous: Option<PathBuf>,
+    first_seqno: u32,
+    last_seqno: u32,
+}
+
+impl ArchiveOutcome {
+    fn new(path: Option<PathBuf>, result: Result<CheckedArchive>) -> Self {
+        match result {
+            Ok(checked) => Self {
+                path,
+                checked: Some(checked),
+                errors: Vec::new(),
+                gap_before: None,
+            },
+            Err(e) => Self {
+                path,
+                checked: None,
+                errors: vec![e],
+                gap_before: None,
+            },
+        }
+    }
+
+    fn is_valid(&self) -> bool {
+        match &self.checked {
+            Some(checked) => self.errors.is_empty() && !checked.report.has_errors(),
+            None => false,
+        }
+    }
+
+    fn boundary(&self) -> Option<&ArchiveBoundary> {
+        self.checked.as_ref()?.boundary.as_ref()
+    }
+
+    fn into_result(mut self) -> Result<CheckedArchive> {
+        if !self.errors.is_empty() {
+            return Err(self.errors.swap_remove(0));
+        }
+        match self.checked {
+            Some(checked) if checked.report.has_errors() => {
+                anyhow::bail!("Invalid archive:\n{}", checked.report)
+            }
+            Some(checked) => Ok(checked),
+            None => anyhow::bail!("Archive was not checked"),
+        }
+    }
+}
+
 /// Lists all archive package entries
 #[derive(argh::FromArgs)]
 #[argh(subcommand, name = "list")]
@@ -252,56 +612,416 @@ struct CmdList {
     /// ignore invalid entries
     #[argh(switch, short = 'i')]
     ignore_invalid: bool,
+
+    /// print entry header offset, data offset and frame length in bytes
+    #[argh(switch, short = 'o')]
+    offsets: bool,
+
+    /// output format: text, json or ndjson. text is used by default
+    #[argh(option, short = 'f', default = "OutputFormat::Text")]
+    format: OutputFormat,
 }
 
 impl CmdList {
     fn run(self) -> Result<()> {
-        let archive = RawArchive::new(self.path)?;
+        let mut records = Vec::new();
+
+        match &self.path {
+            Some(path) => {
+                let archive = RawArchive::open(path)?;
+                let archive = archive.view()?;
+
+                let mut reader =
+                    ArchivePackageViewReader::new(archive.as_ref()).context("Invalid archive")?;
+                while let Some(entry) = reader.read_next()? {
+                    let offsets = (
+                        entry.header_offset as u64,
+                        entry.data_offset as u64,
+                        entry.frame_len,
+                    );
+                    self.print_entry(entry.name, entry.data.len(), offsets, &mut records)?;
+                }
+            }
+            None => {
+                let mut reader =
+                    ArchivePackageStreamReader::new(decompressing_reader(std::io::stdin().lock())?)
+                        .context("Invalid archive")?;
+                while let Some(entry) = reader.read_next()? {
+                    let offsets = (entry.header_offset, entry.data_offset, entry.frame_len);
+                    self.print_entry(&entry.name, entry.data.len(), offsets, &mut records)?;
+                }
+            }
+        }
+
+        if self.format == OutputFormat::Json {
+            self.format.print_all(&records)?;
+        }
+
+        Ok(())
+    }
+
+    /// Prints the entry immediately or collects it into `records` for json output
+    fn print_entry(
+        &self,
+        name: &str,
+        size: usize,
+        offsets: EntryOffsets,
+        records: &mut Vec<ListRecord>,
+    ) -> Result<()> {
+        let package_id = PackageEntryId::from_filename(name);
+        if !self.ignore_invalid && package_id.is_err() {
+            return package_id.map(|_| ()).map_err(From::from);
+        }
+
+        match self.format {
+            OutputFormat::Text => {
+                let item = ListEntry {
+                    package_id,
+                    size,
+                    with_size: self.size,
+                    offsets: self.offsets.then_some(offsets),
+                };
+                println!("{item}");
+            }
+            OutputFormat::Json => records.push(ListRecord::new(name, size, offsets, &package_id)),
+            OutputFormat::Ndjson => {
+                self.format
+                    .print_one(&ListRecord::new(name, size, offsets, &package_id))?
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Builds random-access index for the archive
+#[derive(argh::FromArgs)]
+#[argh(subcommand, name = "index")]
+struct CmdIndex {
+    /// path to the archive file
+    #[argh(option)]
+    path: PathBuf,
+
+    /// path to the index file. `<path>.idx` is used if not specified
+    #[argh(option, short = 'o')]
+    output: Option<PathBuf>,
+}
+
+impl CmdIndex {
+    fn run(self) -> Result<()> {
+        let archive = RawArchive::open(&self.path)?;
         let archive = archive.view()?;
 
-        let mut reader =
-            ArchivePackageViewReader::new(archive.as_ref()).context("Invalid archive")?;
+        let index = ArchiveIndex::build(archive.as_ref()).context("Failed to index archive")?;
 
-        while let Some(entry) = reader.read_next()? {
-            let package_id = PackageEntryId::from_filename(entry.name);
-            if !self.ignore_invalid && package_id.is_err() {
-                return package_id.map(|_| ()).map_err(From::from);
+        let output = match self.output {
+            Some(output) => output,
+            None => {
+                let mut output = self.path.into_os_string();
+                output.push(".idx");
+                PathBuf::from(output)
             }
+        };
+
+        let file = File::create(output).context("Failed to create index file")?;
+        index
+            .write_to(std::io::BufWriter::new(file))
+            .context("Failed to write index")?;
+
+        Ok(())
+    }
+}
+
+/// Writes all recoverable entries of the corrupted archive into a new package
+#[derive(argh::FromArgs)]
+#[argh(subcommand, name = "repair")]
+struct CmdRepair {
+    /// path to the corrupted archive file
+    #[argh(option)]
+    path: PathBuf,
+
+    /// path to the repaired archive file
+    #[argh(option, short = 'o')]
+    output: PathBuf,
+}
+
+impl CmdRepair {
+    fn run(self) -> Result<()> {
+        let archive = RawArchive::open(&self.path)?;
+        let archive = archive.view()?;
+
+        let output = File::create(&self.output).context("Failed to create output file")?;
+        let mut writer = ArchivePackageWriter::new(std::io::BufWriter::new(output))?;
+
+        let mut reader = ArchivePackageSalvageReader::new(archive.as_ref());
 
-            let item = ListEntry {
-                package_id,
-                data: entry.data,
-                with_size: self.size,
-            };
-            println!("{item}");
+        let mut entry_count = 0;
+        while let Some(entry) = reader.read_next() {
+            writer
+                .write_entry_view(&entry)
+                .context("Failed to write entry")?;
+            entry_count += 1;
         }
+        writer.finish().context("Failed to write archive")?;
+
+        for range in reader.corrupted_ranges() {
+            eprintln!(
+                "Corrupted bytes {}..{} ({} bytes)",
+                range.start,
+                range.end,
+                range.len()
+            );
+        }
+        eprintln!("Recovered {entry_count} entries");
 
         Ok(())
     }
 }
 
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum OutputFormat {
+    Text,
+    /// Single json document
+    Json,
+    /// One json record per line
+    Ndjson,
+}
+
+impl OutputFormat {
+    fn print_one<T: serde::Serialize>(self, record: &T) -> Result<()> {
+        let output = match self {
+            Self::Json => serde_json::to_string_pretty(record)?,
+            _ => serde_json::to_string(record)?,
+        };
+        println!("{output}");
+        Ok(())
+    }
+
+    fn print_all<T: serde::Serialize>(self, records: &[T]) -> Result<()> {
+        match self {
+            Self::Ndjson => records.iter().try_for_each(|record| self.print_one(record)),
+            _ => self.print_one(&records),
+        }
+    }
+}
+
+impl std::str::FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(Self::Text),
+            "json" => Ok(Self::Json),
+            "ndjson" => Ok(Self::Ndjson),
+            _ => Err(format!("unknown output format: {s}")),
+        }
+    }
+}
+
+/// Archive check result. See README for the schema description
+#[derive(serde::Serialize)]
+struct ArchiveRecord {
+    path: Option<String>,
+    valid: bool,
+    first_mc_seqno: Option<u32>,
+    last_mc_seqno: Option<u32>,
+    key_blocks: Vec<BlockIdRecord>,
+    merges: Vec<BlockIdRecord>,
+    splits: Vec<BlockIdRecord>,
+    first_blocks: Vec<BlockIdRecord>,
+    last_blocks: Vec<BlockIdRecord>,
+    problems: Vec<ProblemRecord>,
+    errors: Vec<String>,
+    gap_before: Option<GapRecord>,
+}
+
+impl From<&ArchiveOutcome> for ArchiveRecord {
+    fn from(outcome: &ArchiveOutcome) -> Self {
+        fn ids(list: Option<&SimpleList>) -> Vec<BlockIdRecord> {
+            list.into_iter()
+                .flat_map(|list| list.ids.iter().map(BlockIdRecord::from))
+                .collect()
+        }
+
+        let checked = outcome.checked.as_ref();
+        let boundary = outcome.boundary();
+
+        Self {
+            path: outcome.path.as_ref().map(|path| path.display().to_string()),
+            valid: outcome.is_valid(),
+            first_mc_seqno: boundary.map(|boundary| boundary.first_mc_block.seq_no),
+            last_mc_seqno: boundary.map(|boundary| boundary.last_mc_block.seq_no),
+            key_blocks: ids(checked.map(|checked| &checked.key_blocks)),
+            merges: ids(checked.map(|checked| &checked.merges)),
+            splits: ids(checked.map(|checked| &checked.splits)),
+            first_blocks: ids(checked.map(|checked| &checked.first_blocks)),
+            last_blocks: ids(checked.map(|checked| &checked.last_blocks)),
+            problems: checked
+                .into_iter()
+                .flat_map(|checked| checked.report.problems.iter().map(ProblemRecord::from))
+                .collect(),
+            errors: outcome.errors.iter().map(|e| format!("{e:#}")).collect(),
+            gap_before: outcome.gap_before.as_ref().map(|gap| GapRecord {
+                previous: gap.previous.as_ref().map(|path| path.display().to_string()),
+                first_mc_seqno: gap.first_seqno,
+                last_mc_seqno: gap.last_seqno,
+            }),
+        }
+    }
+}
+
+#[derive(serde::Serialize)]
+struct GapRecord {
+    previous: Option<String>,
+    first_mc_seqno: u32,
+    last_mc_seqno: u32,
+}
+
+#[derive(serde::Serialize)]
+struct ProblemRecord {
+    severity: String,
+    entry: Option<String>,
+    block_id: Option<BlockIdRecord>,
+    message: String,
+}
+
+impl From<&ValidationProblem> for ProblemRecord {
+    fn from(problem: &ValidationProblem) -> Self {
+        Self {
+            severity: problem.severity.to_string(),
+            entry: problem.entry_name.clone(),
+            block_id: problem.block_id.as_ref().map(BlockIdRecord::from),
+            message: problem.error.to_string(),
+        }
+    }
+}
+
+/// Package entry description. See README for the schema description
+#[derive(serde::Serialize)]
+struct ListRecord {
+    name: String,
+    kind: Option<&'static str>,
+    block_id: Option<BlockIdRecord>,
+    size: usize,
+    header_offset: u64,
+    data_offset: u64,
+    frame_len: usize,
+    error: Option<String>,
+}
+
+impl ListRecord {
+    fn new(
+        name: &str,
+        size: usize,
+        (header_offset, data_offset, frame_len): EntryOffsets,
+        package_id: &Result<PackageEntryId<ton_block::BlockIdExt>, PackageEntryIdError>,
+    ) -> Self {
+        let (kind, block_id, error) = match package_id {
+            Ok(id) => {
+                let kind = match id {
+                    PackageEntryId::Block(_) => "block",
+                    PackageEntryId::Proof(_) => "proof",
+                    PackageEntryId::ProofLink(_) => "prooflink",
+                };
+                (Some(kind), Some(BlockIdRecord::from(id.block_id())), None)
+            }
+            Err(e) => (None, None, Some(e.to_string())),
+        };
+
+        Self {
+            name: name.to_owned(),
+            kind,
+            block_id,
+            size,
+            header_offset,
+            data_offset,
+            frame_len,
+            error,
+        }
+    }
+}
+
+#[derive(serde::Serialize)]
+struct BlockIdRecord {
+    workchain: i32,
+    /// Tagged shard prefix as 16 hex digits
+    shard: String,
+    seqno: u32,
+    root_hash: String,
+    file_hash: String,
+}
+
+impl From<&ton_block::BlockIdExt> for BlockIdRecord {
+    fn from(id: &ton_block::BlockIdExt) -> Self {
+        Self {
+            workchain: id.shard_id.workchain_id(),
+            shard: format!("{:016x}", id.shard_id.shard_prefix_with_tag()),
+            seqno: id.seq_no,
+            root_hash: hex::encode(id.root_hash.as_slice()),
+            file_hash: hex::encode(id.file_hash.as_slice()),
+        }
+    }
+}
+
+/// Checks whether the file starts with the index sidecar prefix
+fn is_index_file(path: &Path) -> Result<bool> {
+    let mut prefix = [0; 4];
+    match File::open(path)?.read_exact(&mut prefix) {
+        Ok(()) => Ok(ArchiveIndex::is_index(&prefix)),
+        Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => Ok(false),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Reads all key blocks of the archive without checking it
+fn read_key_blocks(path: &Path) -> Result<Vec<KeyBlockEntry>> {
+    let archive = RawArchive::open(path)?;
+    let archive = archive.view()?;
+    Ok(ArchiveData::new_lazy(archive.as_ref())?.key_blocks())
+}
+
+fn read_stdin_entries() -> Result<Vec<ArchivePackageEntry>> {
+    let mut reader =
+        ArchivePackageStreamReader::new(decompressing_reader(std::io::stdin().lock())?)
+            .context("Failed to read archive")?
+            .with_strict_eof(true);
+
+    let mut entries = Vec::new();
+    while let Some(entry) = reader.read_next().context("Failed to read archive")? {
+        entries.push(entry);
+    }
+    Ok(entries)
+}
+
 enum RawArchive {
     Bytes(Vec<u8>),
     File(File),
 }
 
 impl RawArchive {
-    fn new(path: Option<PathBuf>) -> Result<Self> {
-        Ok(match path {
-            Some(path) => {
-                let file = std::fs::OpenOptions::new()
-                    .read(true)
-                    .open(path)
-                    .context("Failed to open archive")?;
-                Self::File(file)
-            }
-            None => {
+    fn open(path: &Path) -> Result<Self> {
+        let mut file = std::fs::OpenOptions::new()
+            .read(true)
+            .open(path)
+            .context("Failed to open archive")?;
+
+        let mut magic = Vec::with_capacity(COMPRESSION_MAGIC_LEN);
+        (&mut file)
+            .take(COMPRESSION_MAGIC_LEN as u64)
+            .read_to_end(&mut magic)
+            .context("Failed to read archive")?;
+
+        Ok(match Compression::detect(&magic) {
+            Some(_) => {
+                file.rewind().context("Failed to read archive")?;
+
                 let mut buffer = Vec::new();
-                std::io::stdin()
+                decompressing_reader(std::io::BufReader::new(file))?
                     .read_to_end(&mut buffer)
-                    .context("Failed to read archive")?;
+                    .context("Failed to decompress archive")?;
                 Self::Bytes(buffer)
             }
+            None => Self::File(file),
         })
     }
 
@@ -2,7 +2,7 @@ use std::borrow::Borrow;
 use std::hash::Hash;
 use std::str::FromStr;
 
-#[derive(Debug, Hash, Eq, PartialEq)]
+#[derive(Debug, Clone, Hash, Eq, PartialEq)]
 pub enum PackageEntryId<I> {
     Block(I),
     Proof(I),
@@ -31,6 +31,14 @@ impl<I> PackageEntryId<I>
 where
     I: Borrow<ton_block::BlockIdExt> + Hash,
 {
+    pub fn block_id(&self) -> &ton_block::BlockIdExt {
+        match self {
+            Self::Block(block_id) | Self::Proof(block_id) | Self::ProofLink(block_id) => {
+                block_id.borrow()
+            }
+        }
+    }
+
     fn filename_prefix(&self) -> &'static str {
         match self {
             Self::Block(_) => PACKAGE_ENTRY_BLOCK,
@@ -1,4 +1,7 @@
 use std::fs::File;
+use std::io::Read;
+
+use crate::archive_package::read_full;
 
 pub struct FileView<'a> {
     _file: &'a File,
@@ -10,7 +13,9 @@ impl<'a> FileView<'a> {
     pub fn new(file: &'a File) -> std::io::Result<Self> {
         use std::os::unix::io::AsRawFd;
 
-        let length = file.metadata()?.len() as usize;
+        let length = usize::try_from(file.metadata()?.len()).map_err(|_| {
+            std::io::Error::new(std::io::ErrorKind::InvalidData, "file is too large to map")
+        })?;
 
         // SAFETY: File was opened successfully, file mode is R, offset is aligned
         let ptr = unsafe {
@@ -53,3 +58,54 @@ impl Drop for FileView<'_> {
         }
     }
 }
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Compression {
+    Zstd,
+    Gzip,
+    Xz,
+}
+
+impl Compression {
+    /// Detects compression by the magic bytes at the beginning of the data
+    pub fn detect(data: &[u8]) -> Option<Self> {
+        if data.starts_with(&ZSTD_MAGIC) {
+            Some(Self::Zstd)
+        } else if data.starts_with(&GZIP_MAGIC) {
+            Some(Self::Gzip)
+        } else if data.starts_with(&XZ_MAGIC) {
+            Some(Self::Xz)
+        } else {
+            None
+        }
+    }
+}
+
+/// Wraps reader with the decoder if the data is compressed
+pub fn decompressing_reader<'a, R>(mut reader: R) -> std::io::Result<Box<dyn Read + 'a>>
+where
+    R: Read + 'a,
+{
+    let mut magic = [0; COMPRESSION_MAGIC_LEN];
+    let filled = read_full(&mut reader, &mut magic)?;
+
+    let compression = Compression::detect(&magic[..filled]);
+
+    // Put magic bytes back in front of the rest of the data
+    let reader = std::io::Cursor::new(magic)
+        .take(filled as u64)
+        .chain(reader);
+
+    Ok(match compression {
+        Some(Compression::Zstd) => Box::new(zstd::Decoder::new(reader)?),
+        Some(Compression::Gzip) => Box::new(flate2::read::MultiGzDecoder::new(reader)),
+        Some(Compression::Xz) => Box::new(xz2::read::XzDecoder::new_multi_decoder(reader)),
+        None => Box::new(reader),
+    })
+}
+
+pub const COMPRESSION_MAGIC_LEN: usize = 6;
+
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+const XZ_MAGIC: [u8; 6] = [0xfd, 0x37, 0x7a, 0x58, 0x5a, 0x00];
@@ -0,0 +1,135 @@
+use crate::archive_data::ArchiveDataError;
+use crate::package_entry_id::*;
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+impl std::fmt::Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Warning => "warning",
+            Self::Error => "error",
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct ValidationProblem {
+    pub severity: Severity,
+    pub block_id: Option<ton_block::BlockIdExt>,
+    pub entry_name: Option<String>,
+    pub error: ArchiveDataError,
+}
+
+impl ValidationProblem {
+    /// Problem which is not related to any specific entry
+    pub fn archive(error: ArchiveDataError) -> Self {
+        Self {
+            severity: Severity::Error,
+            block_id: None,
+            entry_name: None,
+            error,
+        }
+    }
+
+    /// Problem with the block entry
+    pub fn block(block_id: &ton_block::BlockIdExt, error: ArchiveDataError) -> Self {
+        let entry_name = PackageEntryId::Block(block_id).filename();
+        Self {
+            severity: Severity::Error,
+            block_id: Some(block_id.clone()),
+            entry_name: Some(entry_name),
+            error,
+        }
+    }
+
+    /// Problem with the proof (or prooflink) entry
+    pub fn proof(block_id: &ton_block::BlockIdExt, error: ArchiveDataError) -> Self {
+        let entry_name = if block_id.shard_id.is_masterchain() {
+            PackageEntryId::Proof(block_id).filename()
+        } else {
+            PackageEntryId::ProofLink(block_id).filename()
+        };
+        Self {
+            severity: Severity::Error,
+            block_id: Some(block_id.clone()),
+            entry_name: Some(entry_name),
+            error,
+        }
+    }
+
+    pub fn with_severity(mut self, severity: Severity) -> Self {
+        self.severity = severity;
+        self
+    }
+}
+
+impl std::fmt::Display for ValidationProblem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_fmt(format_args!("[{}] ", self.severity))?;
+        if let Some(entry_name) = &self.entry_name {
+            f.write_fmt(format_args!("{entry_name}: "))?;
+        }
+        f.write_fmt(format_args!("{}", self.error))
+    }
+}
+
+/// All problems found in the archive
+#[derive(Debug, Default)]
+pub struct ValidationReport {
+    pub problems: Vec<ValidationProblem>,
+}
+
+impl ValidationReport {
+    pub fn push(&mut self, problem: ValidationProblem) {
+        self.problems.push(problem);
+    }
+
+    pub fn extend(&mut self, other: ValidationReport) {
+        self.problems.extend(other.problems);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.problems.is_empty()
+    }
+
+    pub fn has_errors(&self) -> bool {
+        self.errors().next().is_some()
+    }
+
+    pub fn errors(&self) -> impl Iterator<Item = &ValidationProblem> {
+        self.problems
+            .iter()
+            .filter(|problem| problem.severity == Severity::Error)
+    }
+
+    pub fn warnings(&self) -> impl Iterator<Item = &ValidationProblem> {
+        self.problems
+            .iter()
+            .filter(|problem| problem.severity == Severity::Warning)
+    }
+
+    /// Returns the first error if there is any
+    pub fn into_result(self) -> Result<(), ArchiveDataError> {
+        match self
+            .problems
+            .into_iter()
+            .find(|problem| problem.severity == Severity::Error)
+        {
+            Some(problem) => Err(problem.error),
+            None => Ok(()),
+        }
+    }
+}
+
+impl std::fmt::Display for ValidationReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for problem in &self.problems {
+            f.write_fmt(format_args!("{problem}\n"))?;
+        }
+        Ok(())
+    }
+}